@@ -0,0 +1,213 @@
+//! `--archive`: generate the full asset set and pack it into a zip or tar.gz file.
+
+use crate::browserconfig;
+use crate::cli::{HeadGroup, LinkFormat, StatusBarStyle};
+use crate::error::{FavisError, Result};
+use crate::fingerprint::sha256_hex;
+use crate::generate::{self, FileStatus, GenerateOptions, GenerateReport, OutputFile};
+use crate::link::{self, LinkOptions, LinkSource};
+use crate::manifest::MANIFEST_FILE;
+use crate::stage;
+use crate::stdio;
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use owo_colors::OwoColorize;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{atomic::AtomicBool, Arc};
+
+/// File name of the HTML snippet added to archives
+pub const SNIPPET_FILE: &str = "favicons.html";
+
+/// File name of the Windows tile config added to archives
+pub const BROWSERCONFIG_FILE: &str = "browserconfig.xml";
+
+/// Supported archive formats, picked from the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Detects the format from `favicons.zip`, `favicons.tar.gz` or `favicons.tgz`
+    ///
+    /// Archives streamed to stdout (`-`) are always tar.gz, which unpacks straight from a pipe.
+    pub fn from_path(path: &str) -> Result<Self> {
+        let lower = path.to_lowercase();
+        if stdio::is_stdio(path) {
+            Ok(Self::TarGz)
+        } else if lower.ends_with(".zip") {
+            Ok(Self::Zip)
+        } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else {
+            Err(FavisError::invalid_option(format!(
+                "Unsupported archive \"{path}\": use a .zip, .tar.gz or .tgz file name"
+            )))
+        }
+    }
+}
+
+/// A scratch directory that is removed when dropped
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn new() -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("favis-archive-{}", stage::unique_suffix()));
+        if path.exists() {
+            let _ = fs::remove_dir_all(&path);
+        }
+        fs::create_dir_all(&path).map_err(|err| {
+            FavisError::write_error(format!(
+                "Cannot create temporary directory: {}",
+                path.display()
+            ))
+            .with_source(err)
+        })?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes `names` from `dir` into a zip or tar.gz stream
+pub fn write_archive(
+    dir: &Path,
+    names: &[String],
+    format: ArchiveFormat,
+    mut writer: impl Write,
+) -> Result<()> {
+    let archive_error =
+        |e: &dyn std::fmt::Display| FavisError::write_error(format!("Cannot write archive: {e}"));
+    match format {
+        ArchiveFormat::Zip => {
+            // Zip needs to seek back to patch headers, so build it in memory first;
+            // a full favicon set is only a few hundred kilobytes
+            let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated);
+            for name in names {
+                zip.start_file(name.as_str(), options)
+                    .map_err(|e| archive_error(&e))?;
+                zip.write_all(&fs::read(dir.join(name))?)?;
+            }
+            let buffer = zip.finish().map_err(|e| archive_error(&e))?;
+            writer.write_all(buffer.get_ref())?;
+            writer.flush()?;
+        }
+        ArchiveFormat::TarGz => {
+            let gz = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+            let mut tar = tar::Builder::new(gz);
+            for name in names {
+                tar.append_path_with_name(dir.join(name), name)
+                    .map_err(|e| archive_error(&e))?;
+            }
+            tar.into_inner()
+                .and_then(|gz| gz.finish())
+                .and_then(|mut writer| writer.flush())
+                .map_err(|e| archive_error(&e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Generates every asset into a scratch directory, adds `browserconfig.xml` and an HTML
+/// snippet, and packs it all into `archive_path` (`-` streams it to stdout).
+///
+/// The archive always includes the manifest, since the snippet and browserconfig refer to it.
+/// It is written next to its final path and renamed, so a failed run leaves no partial file.
+pub fn run(
+    options: &GenerateOptions,
+    archive_path: &str,
+    spinner: &ProgressBar,
+    cancelled: Arc<AtomicBool>,
+) -> Result<GenerateReport> {
+    let format = ArchiveFormat::from_path(archive_path)?;
+    if stdio::is_stdio(archive_path) {
+        stdio::ensure_stdout_piped("an archive")?;
+    }
+    let scratch = ScratchDir::new()?;
+    let dir = scratch.0.clone();
+
+    let options = GenerateOptions {
+        output: dir.to_string_lossy().into_owned(),
+        manifest: true,
+        force: true,
+        ..options.clone()
+    };
+    let mut report = generate::run(&options, spinner, cancelled)?;
+
+    spinner.set_message(format!(
+        "{}",
+        "Writing browserconfig.xml and HTML snippet..."
+            .cyan()
+            .bold()
+    ));
+    let manifest_path = dir.join(MANIFEST_FILE).to_string_lossy().into_owned();
+    let manifest = link::read_manifest(&manifest_path)?;
+    fs::write(
+        dir.join(BROWSERCONFIG_FILE),
+        browserconfig::render(&manifest),
+    )?;
+
+    let link_options = LinkOptions {
+        base_url: None,
+        format: LinkFormat::Html,
+        groups: HeadGroup::value_variants().to_vec(),
+        status_bar_style: StatusBarStyle::Default,
+    };
+    let snippet_path = dir.join(SNIPPET_FILE).to_string_lossy().into_owned();
+    link::generate_links(
+        LinkSource::Manifest(&manifest_path),
+        &link_options,
+        Some(&snippet_path),
+        None,
+    )?;
+
+    // Entries are listed by name inside the archive; the lock file stays behind
+    for file in &mut report.files {
+        if let Some(name) = Path::new(&file.path).file_name() {
+            file.path = name.to_string_lossy().into_owned();
+        }
+    }
+    for name in [BROWSERCONFIG_FILE, SNIPPET_FILE] {
+        let bytes = fs::read(dir.join(name))?;
+        report.files.push(OutputFile {
+            path: name.to_string(),
+            sizes: Vec::new(),
+            bytes: bytes.len() as u64,
+            sha256: sha256_hex(&bytes),
+            status: FileStatus::Written,
+        });
+    }
+    let names: Vec<String> = report.files.iter().map(|file| file.path.clone()).collect();
+
+    spinner.set_message(format!(
+        "{} {}",
+        "Packing archive".cyan().bold(),
+        archive_path.yellow()
+    ));
+    if stdio::is_stdio(archive_path) {
+        write_archive(&dir, &names, format, io::stdout().lock())?;
+    } else {
+        let partial = format!("{archive_path}.partial");
+        let result = File::create(&partial)
+            .map_err(|err| FavisError::write_error(&partial).with_source(err))
+            .and_then(|file| write_archive(&dir, &names, format, io::BufWriter::new(file)))
+            .and_then(|_| fs::rename(&partial, archive_path).map_err(FavisError::from));
+        if let Err(err) = result {
+            let _ = fs::remove_file(&partial);
+            return Err(err);
+        }
+    }
+
+    report.output_dir = archive_path.to_string();
+    report.archive = Some(archive_path.to_string());
+    Ok(report)
+}
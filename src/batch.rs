@@ -0,0 +1,280 @@
+//! Batch mode: run every job from a config file, side by side, and summarize the results.
+
+use crate::config::Config;
+use crate::error::{ErrorKind, FavisError, Result};
+use crate::generate::{self, FileStatus, GenerateReport};
+use crate::output::Output;
+use crate::progress::create_job_spinner;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How one job ended
+#[derive(Debug, Serialize)]
+pub struct JobOutcome {
+    pub name: String,
+    pub source: String,
+    pub ok: bool,
+    #[serde(flatten)]
+    pub report: Option<GenerateReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<FavisError>,
+}
+
+/// Results of all jobs, in config order
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    pub jobs: Vec<JobOutcome>,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// The directory `path` points to: canonical if it exists, otherwise with `.` and `..`
+/// resolved lexically
+fn resolve_dir(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if resolved.file_name().is_some() => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+/// Indexes of the first two jobs with the same output directory, if any
+fn shared_output(outputs: &[PathBuf]) -> Option<(usize, usize)> {
+    outputs.iter().enumerate().find_map(|(second, output)| {
+        let first = outputs[..second].iter().position(|other| other == output)?;
+        Some((first, second))
+    })
+}
+
+impl BatchReport {
+    /// Exit code for a run with failed jobs: 130 if it was cancelled, the failed jobs' own
+    /// code if they all failed the same way, and 1 otherwise
+    pub fn exit_code(&self) -> i32 {
+        let mut errors = self.jobs.iter().filter_map(|job| job.error.as_ref());
+        if errors.clone().any(FavisError::is_cancelled) {
+            return ErrorKind::Cancelled.exit_code();
+        }
+        let first = errors.next().map(|err| err.kind);
+        match first {
+            Some(kind) if errors.all(|err| err.kind == kind) => kind.exit_code(),
+            _ => 1,
+        }
+    }
+}
+
+/// Runs every job in `config_path`, at most `parallel` at a time.
+///
+/// A failing job does not stop the others; Ctrl+C cancels running jobs and skips the rest.
+pub fn run(
+    config_path: &str,
+    parallel: Option<usize>,
+    force: bool,
+    output: &Output,
+    cancelled: Arc<AtomicBool>,
+) -> Result<BatchReport> {
+    let config = Config::load(config_path)?;
+    if config.jobs.is_empty() {
+        return Err(FavisError::invalid_config(
+            config_path,
+            "no jobs found; add a \"jobs\" list",
+        ));
+    }
+
+    // Paths in the config are relative to the config file
+    let base_dir = Path::new(config_path).parent().unwrap_or(Path::new(""));
+    // Jobs sharing an output directory would overwrite each other's files and lock file
+    let outputs: Vec<PathBuf> = config
+        .jobs
+        .iter()
+        .map(|job| resolve_dir(&base_dir.join(&job.output)))
+        .collect();
+    if let Some((first, second)) = shared_output(&outputs) {
+        return Err(FavisError::invalid_config(
+            config_path,
+            format!(
+                "jobs \"{}\" and \"{}\" both write to {}; give every job its own output",
+                config.jobs[first].label(),
+                config.jobs[second].label(),
+                outputs[first].display()
+            ),
+        ));
+    }
+
+    let multi = MultiProgress::new();
+    if !output.shows_progress() {
+        multi.set_draw_target(ProgressDrawTarget::hidden());
+    }
+
+    let queue: VecDeque<(usize, ProgressBar)> = config
+        .jobs
+        .iter()
+        .enumerate()
+        .map(|(index, job)| (index, multi.add(create_job_spinner(job.label()))))
+        .collect();
+    let queue = Mutex::new(queue);
+    let results: Mutex<Vec<Option<Result<GenerateReport>>>> =
+        Mutex::new(config.jobs.iter().map(|_| None).collect());
+
+    let workers = parallel
+        .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .min(config.jobs.len());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let Some((index, bar)) = queue.lock().expect("queue lock").pop_front() else {
+                    break;
+                };
+                let job = &config.jobs[index];
+
+                let result = if cancelled.load(Ordering::Relaxed) {
+                    Err(FavisError::user_cancelled())
+                } else {
+                    bar.enable_steady_tick(Duration::from_millis(60));
+                    job.to_options(base_dir, &config.manifest, force)
+                        .and_then(|options| generate::run(&options, &bar, cancelled.clone()))
+                };
+
+                let message = match &result {
+                    Ok(report) => format!(
+                        "{} {}",
+                        "✓".green().bold(),
+                        format!(
+                            "{} written, {} unchanged",
+                            report.count(FileStatus::Written),
+                            report.count(FileStatus::Unchanged)
+                        )
+                        .green()
+                    ),
+                    Err(err) => format!("{} {}", "✗".red().bold(), err.context.red()),
+                };
+                // Without live bars, say which job this line belongs to
+                if bar.is_hidden() {
+                    output.finish(&bar, format!("{} {message}", job.label().bold()));
+                } else {
+                    bar.finish_with_message(message);
+                }
+
+                results.lock().expect("results lock")[index] = Some(result);
+            });
+        }
+    });
+
+    let results = results.into_inner().expect("results lock");
+    let jobs: Vec<JobOutcome> = config
+        .jobs
+        .iter()
+        .zip(results)
+        .map(|(job, result)| {
+            let result = result.unwrap_or_else(|| Err(FavisError::user_cancelled()));
+            let (report, error) = match result {
+                Ok(report) => (Some(report), None),
+                Err(err) => (None, Some(err)),
+            };
+            JobOutcome {
+                name: job.label().to_string(),
+                source: job.source.clone(),
+                ok: error.is_none(),
+                report,
+                error,
+            }
+        })
+        .collect();
+
+    let failed = jobs.iter().filter(|job| !job.ok).count();
+    Ok(BatchReport {
+        succeeded: jobs.len() - failed,
+        failed,
+        jobs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_dot_segments() {
+        assert_eq!(
+            resolve_dir(Path::new("no-such-dir/sites/./acme/../acme")),
+            PathBuf::from("no-such-dir/sites/acme")
+        );
+        assert_eq!(
+            resolve_dir(Path::new("no-such-dir/../../up")),
+            PathBuf::from("../up")
+        );
+    }
+
+    #[test]
+    fn finds_jobs_sharing_an_output() {
+        let outputs: Vec<PathBuf> = ["sites/acme", "sites/globex", "sites/./acme/"]
+            .iter()
+            .map(|path| resolve_dir(Path::new("no-such-dir").join(path).as_path()))
+            .collect();
+        assert_eq!(shared_output(&outputs), Some((0, 2)));
+        assert_eq!(shared_output(&outputs[..2]), None);
+    }
+
+    fn report(errors: Vec<Option<FavisError>>) -> BatchReport {
+        let jobs: Vec<JobOutcome> = errors
+            .into_iter()
+            .enumerate()
+            .map(|(i, error)| JobOutcome {
+                name: format!("job {i}"),
+                source: "logo.svg".to_string(),
+                ok: error.is_none(),
+                report: None,
+                error,
+            })
+            .collect();
+        let failed = jobs.iter().filter(|job| !job.ok).count();
+        BatchReport {
+            succeeded: jobs.len() - failed,
+            failed,
+            jobs,
+        }
+    }
+
+    #[test]
+    fn exit_code_is_the_shared_kind_of_the_failures() {
+        let missing = || Some(FavisError::file_not_found("logo.svg"));
+        let report = report(vec![None, missing(), missing()]);
+        assert_eq!(report.exit_code(), ErrorKind::NotFound.exit_code());
+    }
+
+    #[test]
+    fn mixed_failures_exit_with_1() {
+        let report = report(vec![
+            Some(FavisError::file_not_found("logo.svg")),
+            Some(FavisError::image_too_small(64)),
+        ]);
+        assert_eq!(report.exit_code(), 1);
+    }
+
+    #[test]
+    fn cancellation_wins_over_other_failures() {
+        let report = report(vec![
+            Some(FavisError::image_too_small(64)),
+            Some(FavisError::user_cancelled()),
+            None,
+        ]);
+        assert_eq!(report.exit_code(), 130);
+    }
+}
@@ -0,0 +1,51 @@
+//! `browserconfig.xml` for Windows start screen tiles.
+
+use crate::link::{escape_attr, Manifest};
+
+/// Tile slots and the size each one is drawn at
+const TILES: &[(&str, u32)] = &[
+    ("square70x70logo", 70),
+    ("square150x150logo", 150),
+    ("square310x310logo", 310),
+];
+
+/// Renders `browserconfig.xml` from the icons and theme color of a generated manifest
+///
+/// Each tile uses the smallest PNG icon at least as large as the tile, so Windows only ever
+/// scales down. Tiles without a large enough icon are left out.
+pub fn render(manifest: &Manifest) -> String {
+    let mut icons: Vec<(u32, &str)> = manifest
+        .icons
+        .iter()
+        .filter_map(|icon| {
+            let (w, h) = icon.sizes.as_deref()?.split_once('x')?;
+            let size: u32 = w.parse().ok()?;
+            (h.parse() == Ok(size)).then_some((size, icon.src.as_str()))
+        })
+        .collect();
+    icons.sort_unstable();
+
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="utf-8"?>"#.to_string(),
+        "<browserconfig>".to_string(),
+        "  <msapplication>".to_string(),
+        "    <tile>".to_string(),
+    ];
+    for (tile, tile_size) in TILES {
+        if let Some((_, src)) = icons.iter().find(|(size, _)| size >= tile_size) {
+            lines.push(format!(r#"      <{tile} src="{}"/>"#, escape_attr(src)));
+        }
+    }
+    if let Some(color) = &manifest.theme_color {
+        lines.push(format!(
+            "      <TileColor>{}</TileColor>",
+            escape_attr(color)
+        ));
+    }
+    lines.extend([
+        "    </tile>".to_string(),
+        "  </msapplication>".to_string(),
+        "</browserconfig>".to_string(),
+    ]);
+    lines.join("\n") + "\n"
+}
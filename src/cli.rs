@@ -1,6 +1,8 @@
 //! CLI argument definitions for favis
 
-use clap::{Parser, Subcommand, ValueEnum};
+use crate::icon_sizes::IconPriority;
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
 
 #[derive(Parser)]
 #[command(
@@ -13,18 +15,21 @@ What it does:
   - Generates standard-sized PNG favicons automatically
   - Bundles them into a multi-resolution favicon.ico file
   - Can generate a web manifest for PWAs
-  - Can also create HTML <link> tags from an existing manifest
+  - Can also create HTML <link> tags from an existing manifest or favicon folder
 
 How to use it:
   > favis generate logo.svg
   > favis generate logo.svg --manifest
   > favis generate logo.svg --coverage extended
   > favis link ./public/manifest.webmanifest
+  > favis link --scan ./public
+  > favis batch brands.json
 
 Tips:
   - Got an SVG? Perfect! It's the best source for clean, scalable icons
   - Use --output to choose where files are saved
   - Run 'favis <SUBCOMMAND> --help' for more options
+  - Add --json in CI for a structured result; colors and the spinner turn off when piped
 ",
     author,
     version,
@@ -33,9 +38,38 @@ Tips:
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// Print a machine-readable JSON result instead of progress and messages
+    #[arg(
+        long,
+        global = true,
+        help = "Print the result (or error) as JSON on stdout, for scripts and CI"
+    )]
+    pub json: bool,
+
+    /// Only print errors
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "Only print errors"
+    )]
+    pub quiet: bool,
+
+    /// Print more detail; repeatable
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Print more detail, such as every file written with its size"
+    )]
+    pub verbose: u8,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum SizeLevel {
     /// Only required sizes (minimal set, fastest)
     Required,
@@ -45,6 +79,171 @@ pub enum SizeLevel {
     Extended,
 }
 
+impl SizeLevel {
+    /// The icon priority this coverage level stands for
+    pub fn priority(self) -> IconPriority {
+        match self {
+            SizeLevel::Required => IconPriority::Required,
+            SizeLevel::Recommended => IconPriority::Recommended,
+            SizeLevel::Extended => IconPriority::Extended,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LinkFormat {
+    /// Plain HTML <link> tags (default)
+    Html,
+    /// JSON array of tag attributes
+    Json,
+    /// React component (JavaScript)
+    Jsx,
+    /// React component (TypeScript)
+    Tsx,
+    /// Next.js `metadata.icons` object
+    Next,
+    /// Vue/Nuxt `useHead` config
+    Vue,
+    /// Astro component snippet
+    Astro,
+    /// Svelte `<svelte:head>` snippet
+    Svelte,
+    /// Jinja template partial
+    Jinja,
+    /// Handlebars template partial
+    Handlebars,
+}
+
+/// Groups of tags in the generated <head> block
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HeadGroup {
+    /// Icon <link> tags
+    Icons,
+    /// <link rel="manifest">
+    Manifest,
+    /// <meta name="theme-color">
+    Theme,
+    /// Apple web app title, capable and status bar style
+    Apple,
+    /// <meta name="application-name">
+    Application,
+}
+
+/// Status bar appearance for iOS home screen web apps
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StatusBarStyle {
+    /// White status bar with black text
+    Default,
+    /// Black status bar with white text
+    Black,
+    /// Transparent status bar over the page content
+    BlackTranslucent,
+}
+
+/// Web app manifest display modes
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayMode {
+    /// Full screen, no browser UI
+    Fullscreen,
+    /// Looks like a native app
+    Standalone,
+    /// App-like with minimal browser controls
+    MinimalUi,
+    /// Regular browser tab
+    Browser,
+}
+
+/// How generated files are fingerprinted for cache busting
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheBust {
+    /// Put the content hash in the file name (favicon-32x32.3f9a1c0b.png)
+    Name,
+    /// Keep file names and add a query string (favicon-32x32.png?v=3f9a1c0b)
+    Query,
+}
+
+/// Filter used to scale the source down to each icon size
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResampleFilter {
+    /// Sharp, with slight ringing on hard edges (default)
+    Lanczos3,
+    /// Sharp cubic with less ringing than Lanczos
+    CatmullRom,
+    /// Softer cubic with almost no ringing
+    Mitchell,
+    /// Plain area averaging; crisp for pixel art and exact divisions
+    Box,
+}
+
+/// How a non-square source is made square
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fit {
+    /// Center it on a transparent square, keeping all of it (default)
+    Contain,
+    /// Crop the longer side to a centered square
+    Cover,
+    /// Squash it into a square
+    Stretch,
+}
+
+/// How `favis preview` draws images
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PreviewProtocol {
+    /// Use a graphics protocol if the terminal is known to support one, else half-blocks
+    Auto,
+    /// Truecolor half-block characters; works in any modern terminal, including over SSH
+    Halfblocks,
+    /// Kitty graphics protocol (kitty, Ghostty, WezTerm)
+    Kitty,
+    /// iTerm2 inline images (iTerm2, WezTerm)
+    Iterm,
+    /// Sixel graphics (foot, mlterm, xterm -ti vt340, ...)
+    Sixel,
+}
+
+/// What transparent pixels are shown on in `favis preview`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PreviewBackground {
+    /// Gray checkerboard, so transparency is obvious
+    Checker,
+    /// White, like a light browser tab
+    Light,
+    /// Dark gray, like a dark browser tab
+    Dark,
+}
+
+/// Display CLI enums using their command-line spelling
+macro_rules! display_as_value {
+    ($($ty:ty),+) => {
+        $(
+            impl std::fmt::Display for $ty {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    let value = self
+                        .to_possible_value()
+                        .expect("CLI enums have no skipped variants");
+                    f.write_str(value.get_name())
+                }
+            }
+        )+
+    };
+}
+
+display_as_value!(
+    LinkFormat,
+    HeadGroup,
+    StatusBarStyle,
+    DisplayMode,
+    CacheBust,
+    ResampleFilter,
+    Fit,
+    PreviewProtocol,
+    PreviewBackground
+);
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Generate favicon PNGs, ICO, and manifest from a source image
@@ -62,70 +261,46 @@ How to use it:
   > favis generate logo.svg
   > favis generate logo.svg --coverage extended --manifest --output ./public
   > favis generate logo.png --raster-ok
+  > favis generate logo.svg --manifest --name \"Acme\" --display standalone --theme-color auto
 
 Helpful tips:
   - SVGs are ideal — they scale cleanly at any size
   - Use --coverage extended to generate icons for every use case
   - PNGs are fine too — just pass --raster-ok and you're good to go!
+  - Put manifest settings in a JSON file and pass --config to reuse them
+  - Use --cache-bust name with --manifest so browsers pick up a new logo right away
+  - Re-runs only redo files whose inputs changed (tracked in favis.lock); use --force to redo all
+  - Add --watch while designing your logo to regenerate on every save
+  - Use --dry-run before pointing favis at a folder that already has icons
+  - Tab icons look soft? Try --small-filter catmull-rom --sharpen
+  - Icons drawn with currentColor? Pick the color with --current-color
+  - Use --archive favicons.zip to hand the whole set to someone else as one file
+  - Pipe images in and archives out with -, e.g. cat logo.svg | favis generate - -o - | tar xz
 "
     )]
-    Generate {
-        /// Path to the source image file (SVG preferred)
-        #[arg(
-            help = "Source image file — SVG recommended for best quality",
-            value_name = "SOURCE"
-        )]
-        source: String,
-
-        /// Icon size coverage: required, recommended, or extended
-        #[arg(
-            short,
-            long,
-            value_enum,
-            default_value = "recommended",
-            help = "Choose how many icon sizes to generate",
-            value_name = "COVERAGE"
-        )]
-        coverage: SizeLevel,
-
-        /// Also generate a web manifest file
-        #[arg(short, long, help = "Include a manifest.webmanifest file for PWAs")]
-        manifest: bool,
-
-        /// Output directory for generated files
-        #[arg(
-            short,
-            long,
-            default_value = ".",
-            help = "Where to save the generated files (default: current dir)",
-            value_name = "DIR"
-        )]
-        output: String,
-
-        /// Allow raster source images (PNG/JPG) despite quality concerns
-        #[arg(
-            long,
-            help = "Allow raster images like PNG/JPG (lower quality at large sizes)"
-        )]
-        raster_ok: bool,
-    },
+    Generate(Box<GenerateArgs>),
 
     /// Generate HTML <link> tags from a webmanifest file
     #[command(
-        about = "Generate HTML <link> tags from your manifest.webmanifest file",
+        about = "Generate HTML <head> tags from your manifest.webmanifest file",
         long_about = "\
-Need the right HTML <link> tags for your favicon setup? We've got you.
+Need the right HTML <head> tags for your favicon setup? We've got you.
 
 What this command does:
-  - Reads your manifest.webmanifest file
+  - Reads your manifest.webmanifest file (or scans a folder of favicons with --scan)
   - Generates <link> tags with proper rel and size attributes
+  - Adds the manifest link, theme color, application name and Apple web app tags
   - Sorts them by importance and size
   - Lets you add custom URL prefixes (CDNs, asset paths, etc.)
+  - Can output JSON or ready-to-paste snippets for your framework
 
 How to use it:
   > favis link ./public/manifest.webmanifest
   > favis link ./public/manifest.webmanifest --base /assets/icons --output ./public/favicon-links.html
   > favis link ./manifest.webmanifest --base https://cdn.example.com/icons
+  > favis link ./manifest.webmanifest --format next --output ./app/icons.ts
+  > favis link ./manifest.webmanifest --exclude apple,application
+  > favis link --scan ./public
 
 Pro tips:
   - By default, output goes to the terminal — perfect for copy-paste
@@ -133,13 +308,22 @@ Pro tips:
   - Use --base to prefix your icon URLs with a path or CDN
 "
     )]
+    #[command(group(ArgGroup::new("input").required(true).args(["manifest", "scan"])))]
     Link {
         /// Path to the manifest.webmanifest file
         #[arg(
-            help = "Path to your manifest.webmanifest file",
+            help = "Path to your manifest.webmanifest file, or - to read it from stdin",
             value_name = "MANIFEST"
         )]
-        manifest: String,
+        manifest: Option<String>,
+
+        /// Directory to scan for favicon files instead of reading a manifest
+        #[arg(
+            long,
+            help = "Scan a directory for favicon files instead of reading a manifest",
+            value_name = "DIR"
+        )]
+        scan: Option<String>,
 
         /// Base URL path to prefix for all icon links
         #[arg(
@@ -149,7 +333,48 @@ Pro tips:
         )]
         base: Option<String>,
 
-        /// Output HTML file path (default: print to stdout)
+        /// Output format for the generated tags
+        #[arg(
+            short,
+            long,
+            value_enum,
+            default_value = "html",
+            help = "Output format: html, json, jsx, tsx, next, vue, astro, svelte, jinja, handlebars",
+            value_name = "FORMAT"
+        )]
+        format: LinkFormat,
+
+        /// Tag groups to include (default: all)
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            help = "Only output these tag groups: icons, manifest, theme, apple, application",
+            value_name = "GROUPS"
+        )]
+        include: Vec<HeadGroup>,
+
+        /// Tag groups to leave out
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            help = "Leave out these tag groups: icons, manifest, theme, apple, application",
+            value_name = "GROUPS"
+        )]
+        exclude: Vec<HeadGroup>,
+
+        /// Status bar style for iOS home screen web apps
+        #[arg(
+            long,
+            value_enum,
+            default_value = "default",
+            help = "iOS status bar style when the manifest display is app-like",
+            value_name = "STYLE"
+        )]
+        status_bar_style: StatusBarStyle,
+
+        /// Output file path (default: print to stdout)
         #[arg(
             short,
             long,
@@ -157,5 +382,458 @@ Pro tips:
             value_name = "FILE"
         )]
         output: Option<String>,
+
+        /// Show what would be written without writing it
+        #[arg(
+            long,
+            help = "Show whether the output file would be created, overwritten or left unchanged"
+        )]
+        dry_run: bool,
     },
+
+    /// Generate favicons for several sources from one jobs file
+    #[command(
+        about = "Generate favicons for many brands or sites in one go",
+        long_about = "\
+Generate favicons for many brands or sites in one go.
+
+What it does:
+  - Reads a JSON config with a list of jobs (source, output, coverage, colors, metadata)
+  - Runs the jobs side by side with a progress line for each
+  - Keeps going when a job fails and prints a summary at the end
+
+How to use it:
+  > favis batch brands.json
+  > favis batch brands.json --parallel 2 --force
+
+Example brands.json:
+  {
+    \"manifest\": { \"display\": \"standalone\" },
+    \"jobs\": [
+      { \"source\": \"acme/logo.svg\", \"output\": \"sites/acme\", \"metadata\": { \"name\": \"Acme\" } },
+      { \"source\": \"globex/logo.svg\", \"output\": \"sites/globex\", \"coverage\": \"extended\" }
+    ]
+  }
+
+Good to know:
+  - Relative paths are resolved from the config file's folder
+  - The top-level \"manifest\" block holds defaults that every job can override
+  - If jobs failed, the exit code is theirs when they all failed the same way, else 1
+"
+    )]
+    Batch {
+        /// Path to the jobs config file
+        #[arg(help = "JSON config file with a \"jobs\" list", value_name = "CONFIG")]
+        config: String,
+
+        /// How many jobs run at once
+        #[arg(
+            short,
+            long,
+            help = "Number of jobs to run at the same time (default: number of CPUs)",
+            value_name = "N",
+            value_parser = clap::value_parser!(u16).range(1..)
+        )]
+        parallel: Option<u16>,
+
+        /// Regenerate everything, ignoring lock files
+        #[arg(
+            long,
+            help = "Regenerate every file in every job, even unchanged or hand-edited ones"
+        )]
+        force: bool,
+    },
+
+    /// Show icons right in the terminal
+    #[command(
+        about = "Show your icons right in the terminal",
+        long_about = "\
+Show your icons right in the terminal — handy over SSH or in CI logs.
+
+What it does:
+  - Draws a PNG, every size inside an ICO, or every icon in a folder
+  - Uses truecolor half-block characters, which work in any modern terminal
+  - Uses the kitty, iTerm2 or sixel graphics protocol when your terminal supports it
+
+How to use it:
+  > favis preview public/favicon-16x16.png
+  > favis preview public/favicon.ico --background dark
+  > favis preview public --scale 4
+  > favis preview public --protocol sixel
+
+Good to know:
+  - Small icons are scaled up with square pixels, so every pixel stays visible
+  - Transparent areas are shown on a checkerboard unless you pick another --background
+"
+    )]
+    Preview {
+        /// Icon file or folder of icons
+        #[arg(
+            help = "A PNG or ICO file, or a folder of icons",
+            value_name = "FILE_OR_DIR"
+        )]
+        path: String,
+
+        /// How to draw the images
+        #[arg(
+            long,
+            value_enum,
+            default_value = "auto",
+            help = "How to draw images: detect the terminal, or force half-blocks or a graphics protocol",
+            value_name = "PROTOCOL"
+        )]
+        protocol: PreviewProtocol,
+
+        /// Backdrop for transparent pixels
+        #[arg(
+            long,
+            value_enum,
+            default_value = "checker",
+            help = "What transparent pixels are drawn on",
+            value_name = "BACKGROUND"
+        )]
+        background: PreviewBackground,
+
+        /// Pixel magnification
+        #[arg(
+            long,
+            help = "Draw every icon pixel as NxN pixels (default: enough to make small icons readable)",
+            value_name = "N",
+            value_parser = clap::value_parser!(u32).range(1..=16)
+        )]
+        scale: Option<u32>,
+    },
+
+    /// Compare two favicon sets
+    #[command(
+        about = "Compare two sets of favicons size by size",
+        long_about = "\
+Compare two sets of favicons size by size — handy when reviewing a new logo or a favis upgrade.
+
+What it does:
+  - Matches icons by purpose and size, so renamed or fingerprinted files still line up
+  - Lists sizes that were added or removed
+  - Measures every icon both sets have: the largest channel difference, the share
+    of changed pixels, and a structural similarity score (1 means identical)
+  - With --images, writes before/after/highlight pictures of every changed icon
+
+How to use it:
+  > favis diff old-icons public
+  > favis diff old-icons public --images diff-out
+  > favis diff old-icons public --json
+
+Good to know:
+  - Every size inside favicon.ico is compared on its own
+  - Color hidden under fully transparent pixels doesn't count as a change
+"
+    )]
+    Diff {
+        /// Folder with the old icons
+        #[arg(
+            help = "Folder with the icons before the change",
+            value_name = "BEFORE"
+        )]
+        before: String,
+
+        /// Folder with the new icons
+        #[arg(help = "Folder with the icons after the change", value_name = "AFTER")]
+        after: String,
+
+        /// Where to write highlight images
+        #[arg(
+            long,
+            help = "Write a before/after/highlight image of every changed icon into this folder",
+            value_name = "DIR"
+        )]
+        images: Option<String>,
+    },
+}
+
+/// Arguments for the `generate` command
+#[derive(Args)]
+pub struct GenerateArgs {
+    /// Path to the source image file (SVG preferred)
+    #[arg(
+        help = "Source image file — SVG recommended for best quality; use - to read from stdin",
+        value_name = "SOURCE"
+    )]
+    pub source: String,
+
+    /// Icon size coverage: required, recommended, or extended
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value = "recommended",
+        help = "Choose how many icon sizes to generate",
+        value_name = "COVERAGE"
+    )]
+    pub coverage: SizeLevel,
+
+    /// Also generate a web manifest file
+    #[arg(short, long, help = "Include a manifest.webmanifest file for PWAs")]
+    pub manifest: bool,
+
+    /// Output directory for generated files
+    #[arg(
+        short,
+        long,
+        default_value = ".",
+        help = "Where to save the generated files (default: current dir); use - to stream a tar.gz archive to stdout",
+        value_name = "DIR"
+    )]
+    pub output: String,
+
+    /// Allow raster source images (PNG/JPG) despite quality concerns
+    #[arg(
+        long,
+        help = "Allow raster images like PNG/JPG (lower quality at large sizes)"
+    )]
+    pub raster_ok: bool,
+
+    /// Fingerprint outputs with a content hash
+    #[arg(
+        long,
+        value_enum,
+        help = "Add a content hash to file names (name) or URLs (query) so browsers refetch changed icons",
+        value_name = "MODE"
+    )]
+    pub cache_bust: Option<CacheBust>,
+
+    /// Write an HTML contact sheet of the icons
+    #[arg(
+        long,
+        help = "Also write favis-preview.html: every icon on light, dark and transparent backgrounds, plus tab, iOS and Android mockups"
+    )]
+    pub preview: bool,
+
+    /// Regenerate everything, ignoring the lock file
+    #[arg(
+        long,
+        help = "Regenerate every file, even unchanged or hand-edited ones"
+    )]
+    pub force: bool,
+
+    /// How non-square sources are made square
+    #[arg(
+        long,
+        value_enum,
+        default_value = "contain",
+        help = "How a non-square source becomes square: pad it, crop it or stretch it",
+        value_name = "MODE"
+    )]
+    pub fit: Fit,
+
+    /// Trim uniform margins first
+    #[arg(
+        long,
+        help = "Trim transparent or solid-color margins around the source before fitting it"
+    )]
+    pub trim: bool,
+
+    /// Resampling filter for sizes above 48px
+    #[arg(
+        long,
+        value_enum,
+        default_value = "lanczos3",
+        help = "Resampling filter for icons larger than 48px",
+        value_name = "FILTER"
+    )]
+    pub filter: ResampleFilter,
+
+    /// Resampling filter for sizes up to 48px
+    #[arg(
+        long,
+        value_enum,
+        default_value = "lanczos3",
+        help = "Resampling filter for icons up to 48px (try box or catmull-rom for crisper tabs)",
+        value_name = "FILTER"
+    )]
+    pub small_filter: ResampleFilter,
+
+    /// Unsharp-mask small sizes
+    #[arg(
+        long,
+        help = "Sharpen icons up to 48px so they don't look blurry in browser tabs"
+    )]
+    pub sharpen: bool,
+
+    /// Tag PNG outputs as sRGB
+    #[arg(
+        long,
+        help = "Add an sRGB chunk to PNG outputs so browsers don't guess their color space"
+    )]
+    pub srgb_chunk: bool,
+
+    /// Extra font directories for SVG text
+    #[arg(
+        long = "font-dir",
+        help = "Load fonts for SVG <text> from this folder too (repeatable; system fonts are always loaded)",
+        value_name = "DIR"
+    )]
+    pub font_dirs: Vec<String>,
+
+    /// CSS applied to the SVG before rendering
+    #[arg(
+        long,
+        help = "Apply this CSS file on top of the SVG's own styles",
+        value_name = "FILE"
+    )]
+    pub stylesheet: Option<String>,
+
+    /// Value for `currentColor`
+    #[arg(
+        long,
+        help = "Color used wherever the SVG says currentColor, e.g. \"#0f172a\"",
+        value_name = "COLOR"
+    )]
+    pub current_color: Option<String>,
+
+    /// Resolution for physical SVG units
+    #[arg(
+        long,
+        default_value_t = crate::svg::DEFAULT_DPI,
+        help = "Resolution used to convert SVG units like mm, in and pt to pixels",
+        value_name = "DPI"
+    )]
+    pub dpi: f32,
+
+    /// Element of the SVG to render
+    #[arg(
+        long,
+        help = "Render only the SVG element with this id, cropped to its bounds (e.g. mark-dark)",
+        value_name = "ID"
+    )]
+    pub svg_id: Option<String>,
+
+    /// Crop of the SVG in its own units
+    #[arg(
+        long,
+        help = "Crop the SVG to this rectangle in its own units, replacing its viewBox",
+        value_name = "X,Y,W,H",
+        allow_hyphen_values = true
+    )]
+    pub viewbox: Option<crate::svg::ViewBox>,
+
+    /// Largest source side to decode or render
+    #[arg(
+        long,
+        default_value_t = crate::limits::DEFAULT_MAX_DIMENSION,
+        help = "Refuse sources that would decode or render larger than this many pixels on a side",
+        value_name = "PX",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub max_dimension: u32,
+
+    /// Memory budget for processing the source
+    #[arg(
+        long,
+        default_value_t = crate::limits::DEFAULT_MAX_MEMORY,
+        help = "Refuse sources that would need more than this many MiB while being processed (about 24 bytes per source pixel)",
+        value_name = "MIB",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub max_memory: u64,
+
+    /// Keep running and regenerate when the source or config changes
+    #[arg(
+        long,
+        help = "Watch the source and config file and regenerate on every change (Ctrl+C to stop)"
+    )]
+    pub watch: bool,
+
+    /// Show what would be written without writing it
+    #[arg(
+        long,
+        conflicts_with = "watch",
+        help = "List the files that would be created, overwritten or left unchanged, and the manifest changes, without writing anything"
+    )]
+    pub dry_run: bool,
+
+    /// Pack the generated set into a single archive instead of a directory
+    #[arg(
+        long,
+        conflicts_with_all = ["watch", "dry_run", "output"],
+        help = "Write every asset, the manifest, browserconfig.xml and an HTML snippet into one .zip or .tar.gz file",
+        value_name = "FILE"
+    )]
+    pub archive: Option<String>,
+
+    /// Read settings from a JSON config file
+    #[arg(
+        long,
+        help = "Read manifest settings from a JSON config file (flags take precedence)",
+        value_name = "FILE"
+    )]
+    pub config: Option<String>,
+
+    /// App name for the manifest
+    #[arg(long, help = "App name written to the manifest", value_name = "NAME")]
+    pub name: Option<String>,
+
+    /// Short app name for the manifest
+    #[arg(
+        long,
+        help = "Short app name (home screen label) written to the manifest",
+        value_name = "NAME"
+    )]
+    pub short_name: Option<String>,
+
+    /// Start URL for the manifest
+    #[arg(
+        long,
+        help = "URL the installed app opens at, e.g. / or /app",
+        value_name = "URL"
+    )]
+    pub start_url: Option<String>,
+
+    /// Display mode for the manifest
+    #[arg(
+        long,
+        value_enum,
+        help = "How the installed app is shown: fullscreen, standalone, minimal-ui, browser",
+        value_name = "MODE"
+    )]
+    pub display: Option<DisplayMode>,
+
+    /// Theme color for the manifest, or `auto` to pick it from the image
+    #[arg(
+        long,
+        help = "Theme color (CSS color), or 'auto' to pick the brand color from your image",
+        value_name = "COLOR"
+    )]
+    pub theme_color: Option<String>,
+
+    /// Background color for the manifest
+    #[arg(
+        long,
+        help = "Splash screen background color (CSS color)",
+        value_name = "COLOR"
+    )]
+    pub background_color: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    fn parse(args: &[&str]) -> std::result::Result<Cli, clap::Error> {
+        Cli::try_parse_from(["favis", "generate", "logo.svg"].iter().chain(args))
+    }
+
+    /// `--archive` replaces the output directory, so a plan for the directory would be wrong
+    #[test]
+    fn archive_is_refused_with_dry_run_watch_and_output() {
+        for other in [&["--dry-run"][..], &["--watch"], &["--output", "public"]] {
+            let args: Vec<&str> = ["--archive", "out.zip"]
+                .iter()
+                .chain(other)
+                .copied()
+                .collect();
+            let err = parse(&args).err().expect("should be refused");
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{other:?}");
+        }
+        assert!(parse(&["--archive", "out.zip"]).is_ok());
+    }
 }
@@ -0,0 +1,126 @@
+//! Color validation and dominant color extraction for manifest colors.
+
+use crate::error::{FavisError, Result};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Accumulated statistics for one color bucket
+#[derive(Default)]
+struct Bucket {
+    weight: f64,
+    sums: [u64; 3],
+    count: u64,
+}
+
+/// Validates a CSS color string (hex, rgb(), hsl() or a named color)
+pub fn validate_color(field: &str, value: &str) -> Result<()> {
+    normalize_color(field, value).map(|_| ())
+}
+
+/// Parses a CSS color string and returns it as lowercase `#rrggbb` (`#rrggbbaa` if translucent)
+pub fn normalize_color(field: &str, value: &str) -> Result<String> {
+    let color = svgtypes::Color::from_str(value.trim()).map_err(|err| {
+        FavisError::invalid_option(format!(
+            "Invalid {field} \"{value}\": expected a CSS color like #336699, rgb(51, 102, 153) or teal"
+        ))
+        .with_source(err)
+    })?;
+    let hex = format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue);
+    Ok(match color.alpha {
+        255 => hex,
+        alpha => format!("{hex}{alpha:02x}"),
+    })
+}
+
+/// Picks the dominant brand color of an image as a `#rrggbb` hex string
+///
+/// Pixels are grouped into coarse color buckets weighted by opacity and saturation,
+/// so a colorful logo on a white or transparent background yields the logo color.
+/// Returns `None` if the image has no visible pixels.
+pub fn dominant_color(img: &image::DynamicImage) -> Option<String> {
+    // A thumbnail is plenty to find the main color and keeps large sources fast
+    let thumb = img.thumbnail(64, 64).to_rgba8();
+
+    let mut buckets: HashMap<(u8, u8, u8), Bucket> = HashMap::new();
+    for pixel in thumb.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+
+        let max = r.max(g).max(b) as f64;
+        let min = r.min(g).min(b) as f64;
+        let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+
+        // Grays still count a little so monochrome logos get a color too
+        let weight = (a as f64 / 255.0) * (0.1 + saturation);
+
+        let bucket = buckets.entry((r >> 4, g >> 4, b >> 4)).or_default();
+        bucket.weight += weight;
+        bucket.sums[0] += r as u64;
+        bucket.sums[1] += g as u64;
+        bucket.sums[2] += b as u64;
+        bucket.count += 1;
+    }
+
+    let Bucket { sums, count, .. } = buckets
+        .into_values()
+        .max_by(|a, b| a.weight.total_cmp(&b.weight))?;
+    Some(format!(
+        "#{:02x}{:02x}{:02x}",
+        sums[0] / count,
+        sums[1] / count,
+        sums[2] / count
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    #[test]
+    fn rejects_invalid_colors() {
+        for value in ["", "#12", "#ggg", "notacolor", "rgb(1, 2)"] {
+            let err = validate_color("theme color", value).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::InvalidInput, "{value}");
+            assert!(err.to_string().contains("theme color"), "{err}");
+        }
+    }
+
+    #[test]
+    fn normalizes_named_and_hex_colors() {
+        for (value, expected) in [
+            ("teal", "#008080"),
+            ("White", "#ffffff"),
+            ("#369", "#336699"),
+            ("#336699", "#336699"),
+            (" #AbCdEf ", "#abcdef"),
+            ("rgb(51, 102, 153)", "#336699"),
+            ("#33669980", "#33669980"),
+        ] {
+            assert_eq!(normalize_color("theme color", value).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn picks_the_saturated_color_of_a_two_tone_image() {
+        // Three quarters white background, one quarter brand red
+        let img = RgbaImage::from_fn(32, 32, |x, y| {
+            if x < 16 && y < 16 {
+                Rgba([220, 20, 60, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        });
+        let picked = dominant_color(&DynamicImage::ImageRgba8(img));
+        assert_eq!(picked.as_deref(), Some("#dc143c"));
+    }
+
+    #[test]
+    fn finds_no_color_in_a_transparent_image() {
+        let img = RgbaImage::from_pixel(8, 8, Rgba([255, 0, 0, 0]));
+        assert_eq!(dominant_color(&DynamicImage::ImageRgba8(img)), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Optional JSON config file for the `generate` and `batch` commands.
+
+use crate::cli::{CacheBust, Fit, ResampleFilter, SizeLevel};
+use crate::error::{FavisError, Result};
+use crate::generate::GenerateOptions;
+use crate::limits::{self, Limits};
+use crate::manifest::ManifestMetadata;
+use crate::resample::Resampling;
+use crate::svg::{self, SvgOptions};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Contents of a favis config file, e.g. `favis.json`:
+///
+/// ```json
+/// { "manifest": { "name": "Acme", "theme_color": "auto", "display": "standalone" } }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Manifest metadata; command-line flags (or a job's own metadata) take precedence
+    #[serde(default)]
+    pub manifest: ManifestMetadata,
+    /// Jobs for `favis batch`
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+}
+
+/// One entry of the `jobs` list, mirroring the `generate` flags
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// Label shown in progress and the summary; defaults to the output directory
+    pub name: Option<String>,
+    pub source: String,
+    pub output: String,
+    #[serde(default = "default_coverage")]
+    pub coverage: SizeLevel,
+    /// Write or update `manifest.webmanifest` (implied by `metadata`)
+    #[serde(default)]
+    pub manifest: bool,
+    #[serde(default)]
+    pub raster_ok: bool,
+    pub cache_bust: Option<CacheBust>,
+    /// How a non-square source is made square; defaults to `contain`
+    pub fit: Option<Fit>,
+    /// Trim transparent or solid margins before fitting
+    #[serde(default)]
+    pub trim: bool,
+    /// Resampling filter above 48px; defaults to `lanczos3`
+    pub filter: Option<ResampleFilter>,
+    /// Resampling filter up to 48px; defaults to `lanczos3`
+    pub small_filter: Option<ResampleFilter>,
+    /// Unsharp-mask icons up to 48px
+    #[serde(default)]
+    pub sharpen: bool,
+    /// Mark PNG outputs as sRGB
+    #[serde(default)]
+    pub srgb_chunk: bool,
+    /// Write `favis-preview.html` next to the icons
+    #[serde(default)]
+    pub preview: bool,
+    /// Extra font directories for SVG text
+    #[serde(default)]
+    pub font_dirs: Vec<String>,
+    /// CSS file applied to the SVG before rendering
+    pub stylesheet: Option<String>,
+    /// Color that `currentColor` resolves to
+    pub current_color: Option<String>,
+    /// Resolution for physical SVG units; defaults to 96
+    pub dpi: Option<f32>,
+    /// Render only the SVG element with this id
+    pub svg_id: Option<String>,
+    /// Crop of the SVG as `"x,y,width,height"` in its own units
+    pub viewbox: Option<svg::ViewBox>,
+    /// Largest source side to decode or render, in pixels; defaults to 16384
+    pub max_dimension: Option<u32>,
+    /// Memory budget for processing the source, in MiB; defaults to 1024
+    pub max_memory: Option<u64>,
+    /// Manifest metadata for this job, on top of the top-level `manifest` defaults
+    #[serde(default)]
+    pub metadata: ManifestMetadata,
+}
+
+fn default_coverage() -> SizeLevel {
+    SizeLevel::Recommended
+}
+
+impl Job {
+    /// Label for progress lines and the summary
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.output)
+    }
+
+    /// Resolves the job into generate options; relative paths are taken from `base_dir`
+    pub fn to_options(
+        &self,
+        base_dir: &Path,
+        defaults: &ManifestMetadata,
+        force: bool,
+    ) -> Result<GenerateOptions> {
+        let resolve = |path: &str| base_dir.join(path).to_string_lossy().into_owned();
+        let defaults_resampling = Resampling::default();
+        Ok(GenerateOptions {
+            source: resolve(&self.source),
+            output: resolve(&self.output),
+            priority: self.coverage.priority(),
+            manifest: self.manifest,
+            raster_ok: self.raster_ok,
+            metadata: self.metadata.clone().or(defaults.clone()),
+            cache_bust: self.cache_bust,
+            fit: self.fit.unwrap_or(Fit::Contain),
+            trim: self.trim,
+            resampling: Resampling {
+                filter: self.filter.unwrap_or(defaults_resampling.filter),
+                small_filter: self
+                    .small_filter
+                    .unwrap_or(defaults_resampling.small_filter),
+                sharpen: self.sharpen,
+            },
+            srgb_chunk: self.srgb_chunk,
+            preview: self.preview,
+            svg: SvgOptions {
+                font_dirs: self
+                    .font_dirs
+                    .iter()
+                    .map(|dir| base_dir.join(dir))
+                    .collect(),
+                stylesheet: self
+                    .stylesheet
+                    .as_deref()
+                    .map(|path| svg::read_stylesheet(&resolve(path)))
+                    .transpose()?,
+                current_color: self.current_color.clone(),
+                dpi: self.dpi.unwrap_or(svg::DEFAULT_DPI),
+                id: self.svg_id.clone(),
+                viewbox: self.viewbox,
+                ..Default::default()
+            },
+            limits: Limits {
+                max_dimension: self.max_dimension.unwrap_or(limits::DEFAULT_MAX_DIMENSION),
+                max_memory: self.max_memory.unwrap_or(limits::DEFAULT_MAX_MEMORY),
+            },
+            force,
+        })
+    }
+}
+
+impl Config {
+    /// Reads and parses a config file
+    pub fn load(path: &str) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .map_err(|err| FavisError::read_failed(format!("config file {path}"), err))?;
+        serde_json::from_str(&raw)
+            .map_err(|err| FavisError::invalid_config(path, err.to_string()).with_source(err))
+    }
+}
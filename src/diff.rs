@@ -0,0 +1,450 @@
+//! `favis diff`: compare two favicon sets size by size.
+
+use crate::error::{FavisError, Result};
+use crate::img;
+use crate::limits::Limits;
+use crate::scan;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Side of the sliding window the similarity score is computed over
+const SSIM_WINDOW: u32 = 8;
+/// Stabilizers from the SSIM paper, for 8-bit values
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+/// Smallest width each panel of a highlight image is scaled up to
+const HIGHLIGHT_MIN_SIZE: u32 = 128;
+/// Gap between the panels of a highlight image
+const HIGHLIGHT_GAP: u32 = 8;
+
+/// One icon of a set: where it comes from and what it is for
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct IconKey {
+    /// `favicon`, `apple-touch`, `android`, `mstile` or `ico`
+    pub purpose: &'static str,
+    pub size: u32,
+}
+
+/// An icon that only one of the sets has
+#[derive(Debug, Serialize)]
+pub struct LoneIcon {
+    #[serde(flatten)]
+    pub key: IconKey,
+    pub file: String,
+}
+
+/// An icon both sets have
+#[derive(Debug, Serialize)]
+pub struct IconComparison {
+    #[serde(flatten)]
+    pub key: IconKey,
+    pub before: String,
+    pub after: String,
+    /// Largest difference in any channel, 0–255
+    pub max_delta: u8,
+    /// Share of pixels that differ at all, 0–1
+    pub changed_pixels: f64,
+    /// Structural similarity, 1 for identical icons
+    pub ssim: f64,
+    /// Highlight image written with `--images`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
+}
+
+/// Result of comparing two favicon sets
+#[derive(Debug, Default, Serialize)]
+pub struct DiffReport {
+    pub before: String,
+    pub after: String,
+    pub added: Vec<LoneIcon>,
+    pub removed: Vec<LoneIcon>,
+    /// Every icon both sets have, changed or not
+    pub compared: Vec<IconComparison>,
+    pub warnings: Vec<String>,
+}
+
+impl DiffReport {
+    /// Number of icons that differ in any pixel
+    pub fn changed(&self) -> usize {
+        self.compared.iter().filter(|c| c.max_delta > 0).count()
+    }
+
+    /// Prints the comparison to stdout
+    pub fn print(&self) {
+        anstream::println!(
+            "{} {} {} {}",
+            "Comparing".cyan().bold(),
+            self.before.yellow(),
+            "→".cyan(),
+            self.after.yellow()
+        );
+        for icon in &self.removed {
+            anstream::println!(
+                "  {} {}  {}",
+                "-".red().bold(),
+                label(&icon.key),
+                format!("({})", icon.file).dimmed()
+            );
+        }
+        for icon in &self.added {
+            anstream::println!(
+                "  {} {}  {}",
+                "+".green().bold(),
+                label(&icon.key),
+                format!("({})", icon.file).dimmed()
+            );
+        }
+        for icon in &self.compared {
+            if icon.max_delta == 0 {
+                anstream::println!(
+                    "  {} {}  {}",
+                    "=".dimmed(),
+                    label(&icon.key),
+                    "identical".dimmed()
+                );
+                continue;
+            }
+            let highlight = icon
+                .highlight
+                .as_ref()
+                .map(|path| format!(", see {path}"))
+                .unwrap_or_default();
+            anstream::println!(
+                "  {} {}  {}",
+                "~".yellow().bold(),
+                label(&icon.key),
+                format!(
+                    "max Δ {}, {:.1}% of pixels changed, SSIM {:.4}{highlight}",
+                    icon.max_delta,
+                    icon.changed_pixels * 100.0,
+                    icon.ssim
+                )
+                .dimmed()
+            );
+        }
+        for warning in &self.warnings {
+            anstream::eprintln!("{} {}", "Warning:".yellow().bold(), warning);
+        }
+
+        anstream::println!();
+        anstream::println!(
+            "{} changed, {} added, {} removed, {} identical",
+            self.changed().to_string().yellow().bold(),
+            self.added.len().to_string().green().bold(),
+            self.removed.len().to_string().red().bold(),
+            self.compared.len() - self.changed()
+        );
+    }
+}
+
+/// Compares the icons in `before` with those in `after`, writing highlight images into
+/// `images_dir` if given
+pub fn run(before: &str, after: &str, images_dir: Option<&str>) -> Result<DiffReport> {
+    let mut report = DiffReport {
+        before: before.to_string(),
+        after: after.to_string(),
+        ..Default::default()
+    };
+    let mut old = load_set(before, &mut report.warnings)?;
+    let mut new = load_set(after, &mut report.warnings)?;
+    if let Some(dir) = images_dir {
+        fs::create_dir_all(dir).map_err(|err| FavisError::write_error(dir).with_source(err))?;
+    }
+
+    let keys: Vec<IconKey> = old.keys().chain(new.keys()).cloned().collect();
+    for key in keys {
+        match (old.remove(&key), new.remove(&key)) {
+            (Some((before, old_image)), Some((after, new_image))) => {
+                let mut comparison = compare(key, before, after, &old_image, &new_image);
+                if let (Some(dir), true) = (images_dir, comparison.max_delta > 0) {
+                    let path = Path::new(dir).join(format!(
+                        "diff-{}-{}x{}.png",
+                        comparison.key.purpose, comparison.key.size, comparison.key.size
+                    ));
+                    highlight(&old_image, &new_image)
+                        .save(&path)
+                        .map_err(|err| {
+                            FavisError::write_error(path.display().to_string()).with_source(err)
+                        })?;
+                    comparison.highlight = Some(path.to_string_lossy().into_owned());
+                }
+                report.compared.push(comparison);
+            }
+            (Some((file, _)), None) => report.removed.push(LoneIcon { key, file }),
+            (None, Some((file, _))) => report.added.push(LoneIcon { key, file }),
+            // Already handled when the key came up the first time
+            (None, None) => {}
+        }
+    }
+    Ok(report)
+}
+
+/// What an icon file is for, judging by its name
+fn purpose(name: &str) -> &'static str {
+    let lower = name.to_lowercase();
+    if lower.ends_with(".ico") {
+        "ico"
+    } else if lower.contains("apple-touch") {
+        "apple-touch"
+    } else if lower.contains("android-chrome") {
+        "android"
+    } else if lower.contains("mstile") {
+        "mstile"
+    } else {
+        "favicon"
+    }
+}
+
+fn label(key: &IconKey) -> String {
+    format!("{} {}x{}", key.purpose, key.size, key.size)
+}
+
+/// Decodes every PNG and ICO entry in `dir`, keyed by purpose and size
+fn load_set(
+    dir: &str,
+    warnings: &mut Vec<String>,
+) -> Result<BTreeMap<IconKey, (String, RgbaImage)>> {
+    let limits = Limits::default();
+    let scanned = scan::scan_directory(dir, &limits)?;
+    warnings.extend(scanned.warnings.iter().map(|w| format!("{dir}: {w}")));
+
+    let mut icons = BTreeMap::new();
+    let mut loaded = HashSet::new();
+    for entry in scanned.icons {
+        if !loaded.insert(entry.src.clone()) {
+            continue;
+        }
+        let path = Path::new(dir).join(&entry.src);
+        let lower = entry.src.to_lowercase();
+        let name = path.display().to_string();
+        let images = if lower.ends_with(".png") {
+            vec![img::decode_rgba(&fs::read(&path)?, &name, &limits)?]
+        } else if lower.ends_with(".ico") {
+            img::decode_ico(&fs::read(&path)?, &name, &limits)?
+        } else {
+            continue;
+        };
+
+        // Only square icons have a size to match on
+        for image in images.into_iter().filter(|i| i.width() == i.height()) {
+            let key = IconKey {
+                purpose: purpose(&entry.src),
+                size: image.width(),
+            };
+            if let Some((kept, _)) = icons.get(&key) {
+                // An ICO with two entries of one size keeps the first without a warning
+                if *kept == entry.src {
+                    continue;
+                }
+                warnings.push(format!(
+                    "{dir}: {} and {kept} are both {}; comparing {kept}",
+                    entry.src,
+                    label(&key)
+                ));
+                continue;
+            }
+            icons.insert(key, (entry.src.clone(), image));
+        }
+    }
+    Ok(icons)
+}
+
+/// Channels with color weighted by alpha, so invisible color changes don't count
+fn premultiplied(pixel: &Rgba<u8>) -> [u8; 4] {
+    let [r, g, b, a] = pixel.0;
+    let scale = |c: u8| ((c as u32 * a as u32 + 127) / 255) as u8;
+    [scale(r), scale(g), scale(b), a]
+}
+
+fn compare(
+    key: IconKey,
+    before: String,
+    after: String,
+    old: &RgbaImage,
+    new: &RgbaImage,
+) -> IconComparison {
+    let mut max_delta = 0;
+    let mut changed = 0usize;
+    for (a, b) in old.pixels().zip(new.pixels()) {
+        let (a, b) = (premultiplied(a), premultiplied(b));
+        let delta = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| x.abs_diff(*y))
+            .max()
+            .unwrap_or(0);
+        max_delta = max_delta.max(delta);
+        if delta > 0 {
+            changed += 1;
+        }
+    }
+    let pixels = (old.width() * old.height()).max(1) as f64;
+    IconComparison {
+        key,
+        before,
+        after,
+        max_delta,
+        changed_pixels: changed as f64 / pixels,
+        ssim: ssim(old, new),
+        highlight: None,
+    }
+}
+
+/// Mean structural similarity over sliding windows, averaged across the premultiplied channels
+fn ssim(old: &RgbaImage, new: &RgbaImage) -> f64 {
+    let (width, height) = old.dimensions();
+    let window = SSIM_WINDOW.min(width).min(height);
+    let step = (window / 2).max(1);
+    let starts = |len: u32| (0..=len - window).step_by(step as usize);
+
+    let mut total = 0.0;
+    let mut count = 0;
+    for y0 in starts(height) {
+        for x0 in starts(width) {
+            for channel in 0..4 {
+                let samples = || {
+                    (y0..y0 + window).flat_map(move |y| {
+                        (x0..x0 + window).map(move |x| {
+                            (
+                                premultiplied(old.get_pixel(x, y))[channel] as f64,
+                                premultiplied(new.get_pixel(x, y))[channel] as f64,
+                            )
+                        })
+                    })
+                };
+                let n = (window * window) as f64;
+                let (mean_a, mean_b) =
+                    samples().fold((0.0, 0.0), |(sa, sb), (a, b)| (sa + a, sb + b));
+                let (mean_a, mean_b) = (mean_a / n, mean_b / n);
+                let (var_a, var_b, covariance) =
+                    samples().fold((0.0, 0.0, 0.0), |(va, vb, cov), (a, b)| {
+                        let (da, db) = (a - mean_a, b - mean_b);
+                        (va + da * da, vb + db * db, cov + da * db)
+                    });
+                let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+                total += ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                    / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (var_a + var_b + SSIM_C2));
+                count += 1;
+            }
+        }
+    }
+    total / count.max(1) as f64
+}
+
+/// Before, after and a highlight panel side by side, scaled up with square pixels.
+///
+/// The highlight shows the new icon faded to gray, with changed pixels in red: the bigger
+/// the change, the stronger the red.
+fn highlight(old: &RgbaImage, new: &RgbaImage) -> RgbaImage {
+    let size = old.width();
+    let scale = HIGHLIGHT_MIN_SIZE.div_ceil(size).max(1);
+    let panel = size * scale;
+
+    let mut marked = RgbaImage::new(size, size);
+    for (x, y, pixel) in marked.enumerate_pixels_mut() {
+        let (a, b) = (
+            premultiplied(old.get_pixel(x, y)),
+            premultiplied(new.get_pixel(x, y)),
+        );
+        let delta = a
+            .iter()
+            .zip(&b)
+            .map(|(p, q)| p.abs_diff(*q))
+            .max()
+            .unwrap_or(0);
+        *pixel = if delta > 0 {
+            // Even the smallest change stays visible
+            Rgba([255, 0, 0, 96 + (delta as u32 * 159 / 255) as u8])
+        } else {
+            let [r, g, b, alpha] = new.get_pixel(x, y).0;
+            let gray = ((r as u32 * 30 + g as u32 * 59 + b as u32 * 11) / 100) as u8;
+            Rgba([gray, gray, gray, alpha / 4])
+        };
+    }
+
+    let mut sheet = RgbaImage::new(panel * 3 + HIGHLIGHT_GAP * 2, panel);
+    for (i, image) in [old, new, &marked].into_iter().enumerate() {
+        let scaled = imageops::resize(image, panel, panel, FilterType::Nearest);
+        let x = i as u32 * (panel + HIGHLIGHT_GAP);
+        imageops::replace(&mut sheet, &scaled, x.into(), 0);
+    }
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stage::TempDir;
+    use ico::{IconDir, IconDirEntry, IconImage, ResourceType};
+
+    fn key(purpose: &'static str, size: u32) -> IconKey {
+        IconKey { purpose, size }
+    }
+
+    /// A 16x16 icon with a dark square in the middle
+    fn icon(color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_fn(16, 16, |x, y| {
+            if (4..12).contains(&x) && (4..12).contains(&y) {
+                Rgba(color)
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn identical_icons_compare_equal() {
+        let image = icon([20, 40, 60, 255]);
+        let result = compare(key("favicon", 16), "a".into(), "b".into(), &image, &image);
+
+        assert_eq!(result.ssim, 1.0);
+        assert_eq!(result.max_delta, 0);
+        assert_eq!(result.changed_pixels, 0.0);
+    }
+
+    #[test]
+    fn changed_icons_report_how_much_changed() {
+        let (old, new) = (icon([20, 40, 60, 255]), icon([220, 40, 60, 255]));
+        let result = compare(key("favicon", 16), "a".into(), "b".into(), &old, &new);
+
+        assert_eq!(result.max_delta, 200);
+        assert_eq!(result.changed_pixels, 0.25);
+        assert!(result.ssim < 0.9, "{}", result.ssim);
+    }
+
+    #[test]
+    fn names_each_file_once_when_icons_share_a_size() {
+        let dir = TempDir::new("diff-set");
+        let mut icon_dir = IconDir::new(ResourceType::Icon);
+        for size in [32, 16, 32] {
+            let image = IconImage::from_rgba_data(size, size, vec![0; (size * size * 4) as usize]);
+            icon_dir.add_entry(IconDirEntry::encode(&image).unwrap());
+        }
+        icon_dir
+            .write(fs::File::create(dir.path().join("favicon.ico")).unwrap())
+            .unwrap();
+        for name in ["favicon-32x32.png", "icon-32.png"] {
+            RgbaImage::new(32, 32).save(dir.path().join(name)).unwrap();
+        }
+
+        let mut warnings = Vec::new();
+        let icons = load_set(&dir.path_str(), &mut warnings).unwrap();
+
+        assert_eq!(
+            icons.keys().cloned().collect::<Vec<_>>(),
+            [key("favicon", 32), key("ico", 16), key("ico", 32)]
+        );
+        assert_eq!(
+            warnings,
+            [format!(
+                "{}: icon-32.png and favicon-32x32.png are both favicon 32x32; comparing favicon-32x32.png",
+                dir.path_str()
+            )]
+        );
+    }
+}
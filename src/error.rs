@@ -1,26 +1,98 @@
 //! Centralized error handling with helpful recovery suggestions.
 
 use owo_colors::OwoColorize;
+use serde::{Serialize, Serializer};
+use std::error::Error;
 use std::fmt;
 
+/// What went wrong, independent of the wording; decides the process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Anything else that failed while processing (exit code 1)
+    Processing,
+    /// A flag, config file or manifest value is invalid (exit code 2, like usage errors)
+    InvalidInput,
+    /// A file or directory doesn't exist (exit code 3)
+    NotFound,
+    /// The operating system refused access (exit code 4)
+    PermissionDenied,
+    /// The file isn't in a format favis can read or accept (exit code 5)
+    UnsupportedFormat,
+    /// The SVG can't be parsed or rendered as asked (exit code 6)
+    InvalidSvg,
+    /// The source image is too small for the icons (exit code 7)
+    TooSmall,
+    /// The source is over the decode or render limits (exit code 8)
+    LimitExceeded,
+    /// An output couldn't be written (exit code 9)
+    WriteFailed,
+    /// Outputs were edited by hand and would be overwritten (exit code 10)
+    Conflict,
+    /// The run was interrupted with Ctrl+C (exit code 130, as for SIGINT)
+    Cancelled,
+}
+
+impl ErrorKind {
+    /// Process exit code for errors of this kind
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Processing => 1,
+            Self::InvalidInput => 2,
+            Self::NotFound => 3,
+            Self::PermissionDenied => 4,
+            Self::UnsupportedFormat => 5,
+            Self::InvalidSvg => 6,
+            Self::TooSmall => 7,
+            Self::LimitExceeded => 8,
+            Self::WriteFailed => 9,
+            Self::Conflict => 10,
+            Self::Cancelled => 130,
+        }
+    }
+}
+
 /// Custom error type with context and recovery suggestions
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct FavisError {
+    pub kind: ErrorKind,
+    #[serde(rename = "message")]
     pub context: String,
     pub suggestion: Option<String>,
+    /// Lower-level error this one was made from, if any
+    #[serde(
+        rename = "cause",
+        serialize_with = "serialize_source",
+        skip_serializing_if = "Option::is_none"
+    )]
+    source: Option<Box<dyn Error + Send + Sync>>,
 }
 
 impl FavisError {
-    pub fn new(context: impl Into<String>, suggestion: Option<String>) -> Self {
+    pub fn new(kind: ErrorKind, context: impl Into<String>, suggestion: Option<String>) -> Self {
         Self {
+            kind,
             context: context.into(),
             suggestion,
+            source: None,
         }
     }
 
+    /// Records the lower-level error behind this one
+    pub fn with_source(mut self, source: impl Error + Send + Sync + 'static) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Whether the run was interrupted rather than failed
+    pub fn is_cancelled(&self) -> bool {
+        self.kind == ErrorKind::Cancelled
+    }
+
     pub fn file_not_found(path: impl Into<String>) -> Self {
         let context = format!("Hmm, can't find that file: {}", path.into());
         Self::new(
+            ErrorKind::NotFound,
             context,
             Some("Double-check the path and make sure the file exists!".to_string()),
         )
@@ -29,6 +101,7 @@ impl FavisError {
     pub fn permission_denied(path: impl Into<String>) -> Self {
         let context = format!("Permission denied: {}", path.into());
         Self::new(
+            ErrorKind::PermissionDenied,
             context,
             Some(
                 "Try running with elevated permissions or check file/directory permissions."
@@ -37,6 +110,17 @@ impl FavisError {
         )
     }
 
+    /// A file that couldn't be read; the kind follows the I/O error, which is kept as the source
+    pub fn read_failed(path: impl Into<String>, err: std::io::Error) -> Self {
+        let path = path.into();
+        let error = match err.kind() {
+            std::io::ErrorKind::NotFound => Self::file_not_found(path),
+            std::io::ErrorKind::PermissionDenied => Self::permission_denied(path),
+            _ => Self::processing_error(format!("Cannot read {path}")),
+        };
+        error.with_source(err)
+    }
+
     pub fn invalid_format(details: impl Into<String>) -> Self {
         let context = details.into();
         let suggestion = if context.contains("isn't supported") {
@@ -46,13 +130,35 @@ impl FavisError {
         } else {
             "Use an SVG file for best results, or PNG with --raster-ok."
         };
-        Self::new(context, Some(suggestion.to_string()))
+        Self::new(
+            ErrorKind::UnsupportedFormat,
+            context,
+            Some(suggestion.to_string()),
+        )
+    }
+
+    pub fn invalid_option(details: impl Into<String>) -> Self {
+        Self::new(
+            ErrorKind::InvalidInput,
+            details,
+            Some("Run 'favis <SUBCOMMAND> --help' to see the accepted values.".to_string()),
+        )
+    }
+
+    pub fn invalid_config(path: impl Into<String>, details: impl Into<String>) -> Self {
+        let context = format!("Config file trouble in {}: {}", path.into(), details.into());
+        Self::new(
+            ErrorKind::InvalidInput,
+            context,
+            Some("Check the file is valid JSON and only uses supported keys.".to_string()),
+        )
     }
 
     pub fn image_too_small(min_size: u32) -> Self {
         let context =
             format!("Oops! Image is too small - needs to be at least {min_size}x{min_size} pixels");
         Self::new(
+            ErrorKind::TooSmall,
             context,
             Some(
                 "Try a larger source image or use an SVG for crisp results at any size!"
@@ -61,17 +167,51 @@ impl FavisError {
         )
     }
 
+    pub fn limit_exceeded(details: impl Into<String>) -> Self {
+        Self::new(
+            ErrorKind::LimitExceeded,
+            format!("Too big to process: {}", details.into()),
+            Some(
+                "Use a smaller source, or raise --max-dimension / --max-memory if you trust this file."
+                    .to_string(),
+            ),
+        )
+    }
+
     pub fn invalid_svg(reason: impl Into<String>) -> Self {
         let context = format!("SVG trouble: {}", reason.into());
         Self::new(
+            ErrorKind::InvalidSvg,
             context,
             Some("Check the SVG syntax or try a different SVG file.".to_string()),
         )
     }
 
+    pub fn svg_element_not_found(id: &str, available: &[String]) -> Self {
+        let context = format!("SVG trouble: no element with id \"{id}\" was rendered");
+        let suggestion = if available.is_empty() {
+            "The SVG has no elements with ids; elements that are hidden or only defined in <defs> can't be picked.".to_string()
+        } else {
+            format!(
+                "Pick one of: {}. Elements that are hidden or only defined in <defs> can't be picked.",
+                available.join(", ")
+            )
+        };
+        Self::new(ErrorKind::InvalidSvg, context, Some(suggestion))
+    }
+
+    pub fn unsupported_preview(file: &str) -> Self {
+        Self::new(
+            ErrorKind::UnsupportedFormat,
+            format!("Cannot preview {file}: it isn't a readable PNG or ICO file"),
+            Some("Point favis preview at a PNG, an ICO or a folder of icons.".to_string()),
+        )
+    }
+
     pub fn write_error(path: impl Into<String>) -> Self {
         let context = format!("Can't write to: {}", path.into());
         Self::new(
+            ErrorKind::WriteFailed,
             context,
             Some(
                 "Make sure the output directory exists and you have write permissions.".to_string(),
@@ -79,6 +219,33 @@ impl FavisError {
         )
     }
 
+    pub fn outputs_modified(files: &[String]) -> Self {
+        let context = format!(
+            "These files were edited since favis generated them: {}",
+            files.join(", ")
+        );
+        Self::new(
+            ErrorKind::Conflict,
+            context,
+            Some(
+                "Re-run with --force to overwrite them, or move your edits into the source image."
+                    .to_string(),
+            ),
+        )
+    }
+
+    pub fn unreadable_lock(path: impl Into<String>) -> Self {
+        let context = format!("Cannot read the lock file {}", path.into());
+        Self::new(
+            ErrorKind::InvalidInput,
+            context,
+            Some(
+                "Delete it, or re-run with --force to regenerate everything and write a new one."
+                    .to_string(),
+            ),
+        )
+    }
+
     pub fn processing_error(details: impl Into<String>) -> Self {
         let context = format!("Processing hiccup: {}", details.into());
         let suggestion = if context.contains("memory") || context.contains("allocation") {
@@ -86,31 +253,32 @@ impl FavisError {
         } else {
             Some("Check system resources and give it another shot!".to_string())
         };
-        Self::new(context, suggestion)
+        Self::new(ErrorKind::Processing, context, suggestion)
     }
 
     pub fn user_cancelled() -> Self {
         Self::new(
+            ErrorKind::Cancelled,
             "Operation cancelled by user",
             Some("All partial files have been cleaned up automatically.".to_string()),
         )
     }
 
-    // TODO: Use for signal-based interruption handling
-    #[allow(dead_code)]
-    pub fn interrupted(details: impl Into<String>) -> Self {
-        let context = format!("Process interrupted: {}", details.into());
-        Self::new(
-            context,
-            Some("Cleaning up temporary files and partial outputs".to_string()),
-        )
-    }
-
-    /// Display user-friendly error message with colors and symbols
+    /// Display user-friendly error message with colors and symbols.
+    ///
+    /// Causes are listed under the message unless it already quotes them.
     pub fn display_friendly(&self) {
-        eprintln!("{} {}", "✗".red().bold(), self.context.red().bold());
+        anstream::eprintln!("{} {}", "✗".red().bold(), self.context.red().bold());
+        let mut cause = self.source();
+        while let Some(err) = cause {
+            let text = err.to_string();
+            if !self.context.contains(&text) {
+                anstream::eprintln!("  {} {}", "caused by:".dimmed(), text.dimmed());
+            }
+            cause = err.source();
+        }
         if let Some(suggestion) = &self.suggestion {
-            eprintln!("{} {}", "💡".yellow().bold(), suggestion.yellow());
+            anstream::eprintln!("{} {}", "💡".yellow().bold(), suggestion.yellow());
         }
     }
 }
@@ -121,54 +289,142 @@ impl fmt::Display for FavisError {
     }
 }
 
-impl std::error::Error for FavisError {}
+impl Error for FavisError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+/// Writes the cause chain into JSON as one string
+fn serialize_source<S: Serializer>(
+    source: &Option<Box<dyn Error + Send + Sync>>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match source {
+        Some(source) => serializer.serialize_some(&source.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
 
 impl From<std::io::Error> for FavisError {
     fn from(err: std::io::Error) -> Self {
-        match err.kind() {
+        let error = match err.kind() {
             std::io::ErrorKind::NotFound => FavisError::file_not_found(err.to_string()),
             std::io::ErrorKind::PermissionDenied => FavisError::permission_denied(err.to_string()),
             _ => FavisError::processing_error(err.to_string()),
-        }
+        };
+        error.with_source(err)
     }
 }
 
 impl From<image::ImageError> for FavisError {
     fn from(err: image::ImageError) -> Self {
-        match err {
-            image::ImageError::IoError(io_err) => FavisError::from(io_err),
-            image::ImageError::Limits(_) => FavisError::image_too_small(512),
+        let err = match err {
+            image::ImageError::IoError(io_err) => return FavisError::from(io_err),
+            err => err,
+        };
+        let error = match &err {
+            image::ImageError::Limits(_) => {
+                FavisError::limit_exceeded(format!("the decoder stopped: {err}"))
+            }
             image::ImageError::Unsupported(_) => FavisError::invalid_format(err.to_string()),
             _ => FavisError::processing_error(err.to_string()),
-        }
+        };
+        error.with_source(err)
     }
 }
 
 impl From<resvg::usvg::Error> for FavisError {
     fn from(err: resvg::usvg::Error) -> Self {
-        FavisError::invalid_svg(err.to_string())
+        FavisError::invalid_svg(err.to_string()).with_source(err)
     }
 }
 
 impl From<serde_json::Error> for FavisError {
     fn from(err: serde_json::Error) -> Self {
-        FavisError::invalid_format(format!("JSON parsing error: {err}"))
+        FavisError::invalid_format(format!("JSON parsing error: {err}")).with_source(err)
     }
 }
 
-/// Helper macro for creating context-aware errors
-#[allow(unused_macros)]
-macro_rules! context_error {
-    ($kind:expr, $msg:expr) => {
-        FavisError::new($kind, $msg)
-    };
-    ($kind:expr, $msg:expr, $($arg:tt)*) => {
-        FavisError::new($kind, format!($msg, $($arg)*))
-    };
-}
-
-#[allow(unused_imports)]
-pub(crate) use context_error;
-
 /// Result type alias for convenience
 pub type Result<T> = std::result::Result<T, FavisError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io;
+
+    /// Every kind, in declaration order
+    const KINDS: [ErrorKind; 11] = [
+        ErrorKind::Processing,
+        ErrorKind::InvalidInput,
+        ErrorKind::NotFound,
+        ErrorKind::PermissionDenied,
+        ErrorKind::UnsupportedFormat,
+        ErrorKind::InvalidSvg,
+        ErrorKind::TooSmall,
+        ErrorKind::LimitExceeded,
+        ErrorKind::WriteFailed,
+        ErrorKind::Conflict,
+        ErrorKind::Cancelled,
+    ];
+
+    #[test]
+    fn every_kind_has_its_own_nonzero_exit_code() {
+        let codes: HashSet<i32> = KINDS.iter().map(|kind| kind.exit_code()).collect();
+        assert_eq!(codes.len(), KINDS.len());
+        assert!(!codes.contains(&0));
+        assert_eq!(ErrorKind::Cancelled.exit_code(), 130);
+    }
+
+    #[test]
+    fn io_errors_keep_their_kind_and_source() {
+        let err = FavisError::from(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert_eq!(err.kind, ErrorKind::NotFound);
+        assert_eq!(
+            err.source().map(|source| source.to_string()),
+            Some("gone".into())
+        );
+
+        let err = FavisError::from(io::Error::new(io::ErrorKind::PermissionDenied, "no"));
+        assert_eq!(err.kind, ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn json_names_the_kind_and_the_cause() {
+        let err = FavisError::write_error("out")
+            .with_source(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["kind"], "write_failed");
+        assert_eq!(json["cause"], "disk full");
+        assert!(serde_json::to_value(FavisError::user_cancelled()).unwrap()["cause"].is_null());
+    }
+
+    #[test]
+    fn read_failures_follow_the_io_error() {
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        let err = FavisError::read_failed("logo.svg", denied);
+        assert_eq!(
+            err.kind.exit_code(),
+            ErrorKind::PermissionDenied.exit_code()
+        );
+        assert_eq!(
+            err.source().map(|source| source.to_string()),
+            Some("denied".into())
+        );
+
+        let missing = io::Error::new(io::ErrorKind::NotFound, "gone");
+        assert_eq!(
+            FavisError::read_failed("logo.svg", missing).kind,
+            ErrorKind::NotFound
+        );
+        let other = io::Error::new(io::ErrorKind::InvalidData, "bad");
+        assert_eq!(
+            FavisError::read_failed("logo.svg", other).kind,
+            ErrorKind::Processing
+        );
+    }
+}
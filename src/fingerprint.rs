@@ -0,0 +1,204 @@
+//! Content-hashed asset names for cache busting.
+
+use crate::cli::CacheBust;
+use crate::error::{FavisError, Result};
+use crate::stage::Staging;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Number of hex digits of the content hash used in names and query strings
+pub const HASH_LEN: usize = 8;
+
+/// Maps generated file names (e.g. `favicon-32x32.png`) to the name or URL that should
+/// reference them (e.g. `favicon-32x32.3f9a1c0b.png` or `favicon-32x32.png?v=3f9a1c0b`).
+pub type AssetRefs = HashMap<String, String>;
+
+/// Full SHA-256 of some bytes as lowercase hex
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Short content hash used for fingerprints
+pub fn content_hash(bytes: &[u8]) -> String {
+    let mut hash = sha256_hex(bytes);
+    hash.truncate(HASH_LEN);
+    hash
+}
+
+/// Splits `favicon-32x32.png` into `("favicon-32x32", "png")`
+fn split_name(name: &str) -> (&str, &str) {
+    name.rsplit_once('.').unwrap_or((name, ""))
+}
+
+/// Returns true if `candidate` is `<stem>.<hash>.<ext>` for the given stem and extension
+fn is_fingerprint_of(candidate: &str, stem: &str, ext: &str) -> bool {
+    let Some(rest) = candidate
+        .strip_prefix(stem)
+        .and_then(|r| r.strip_prefix('.'))
+        .and_then(|r| r.strip_suffix(ext))
+        .and_then(|r| r.strip_suffix('.'))
+    else {
+        return false;
+    };
+    rest.len() == HASH_LEN && rest.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Schedules fingerprinted copies of `name` left behind by earlier runs for removal, except `keep`
+fn remove_stale(staging: &mut Staging, name: &str, keep: &str) -> Result<()> {
+    let (stem, ext) = split_name(name);
+    for entry in fs::read_dir(staging.out_dir())?.filter_map(|e| e.ok()) {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name != keep && is_fingerprint_of(&file_name, stem, ext) {
+            staging.remove_on_commit(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Reference and on-disk file name for `name` with content hash `hash`
+fn fingerprinted(name: &str, hash: &str, mode: CacheBust) -> (String, String) {
+    match mode {
+        CacheBust::Query => (format!("{name}?v={hash}"), name.to_string()),
+        CacheBust::Name if name.eq_ignore_ascii_case("favicon.ico") => {
+            (name.to_string(), name.to_string())
+        }
+        CacheBust::Name => {
+            let (stem, ext) = split_name(name);
+            let hashed = format!("{stem}.{hash}.{ext}");
+            (hashed.clone(), hashed)
+        }
+    }
+}
+
+/// How `name` would be referenced before its content hash is known, e.g. `favicon-32x32.<hash>.png`
+pub fn placeholder_reference(name: &str, mode: CacheBust) -> String {
+    fingerprinted(name, "<hash>", mode).0
+}
+
+/// Fingerprints the staged files and returns how each one should be referenced.
+///
+/// In `name` mode PNGs are renamed to `<stem>.<hash>.<ext>`; `favicon.ico` keeps its name
+/// because browsers request it by convention. In `query` mode files stay put and references
+/// get a `?v=<hash>` suffix. Either way, fingerprinted copies from earlier runs are removed
+/// when the staging area commits.
+pub fn apply(staging: &mut Staging, files: &[PathBuf], mode: CacheBust) -> Result<AssetRefs> {
+    let dir = staging.dir().to_path_buf();
+    let mut refs = AssetRefs::new();
+
+    for path in files {
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let bytes = fs::read(path).map_err(|err| {
+            FavisError::write_error(format!("Cannot read back {}", path.display())).with_source(err)
+        })?;
+        let hash = content_hash(&bytes);
+
+        let (reference, file_name) = fingerprinted(&name, &hash, mode);
+        if file_name != name {
+            fs::rename(path, dir.join(&file_name)).map_err(|err| {
+                FavisError::write_error(format!("Cannot rename {name} to {file_name}"))
+                    .with_source(err)
+            })?;
+        }
+        remove_stale(staging, &name, &file_name)?;
+        refs.insert(name, reference);
+    }
+
+    Ok(refs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stage::TempDir;
+
+    #[test]
+    fn names_and_queries_carry_the_content_hash() {
+        assert_eq!(
+            fingerprinted("favicon-32x32.png", "3f9a1c0b", CacheBust::Name),
+            (
+                "favicon-32x32.3f9a1c0b.png".to_string(),
+                "favicon-32x32.3f9a1c0b.png".to_string()
+            )
+        );
+        assert_eq!(
+            fingerprinted("favicon-32x32.png", "3f9a1c0b", CacheBust::Query),
+            (
+                "favicon-32x32.png?v=3f9a1c0b".to_string(),
+                "favicon-32x32.png".to_string()
+            )
+        );
+        assert_eq!(
+            placeholder_reference("apple-touch-icon.png", CacheBust::Name),
+            "apple-touch-icon.<hash>.png"
+        );
+    }
+
+    #[test]
+    fn never_renames_favicon_ico() {
+        let (reference, file) = fingerprinted("favicon.ico", "3f9a1c0b", CacheBust::Name);
+        assert_eq!(
+            (reference.as_str(), file.as_str()),
+            ("favicon.ico", "favicon.ico")
+        );
+        let (reference, _) = fingerprinted("favicon.ico", "3f9a1c0b", CacheBust::Query);
+        assert_eq!(reference, "favicon.ico?v=3f9a1c0b");
+    }
+
+    #[test]
+    fn recognises_only_its_own_fingerprints() {
+        let is_ours = |candidate| is_fingerprint_of(candidate, "favicon-32x32", "png");
+        assert!(is_ours("favicon-32x32.3f9a1c0b.png"));
+        assert!(!is_ours("favicon-32x32.png"));
+        assert!(!is_ours("favicon-32x32.3f9a1c0.png"));
+        assert!(!is_ours("favicon-32x32.3f9a1c0g.png"));
+        assert!(!is_ours("favicon-32x32.3f9a1c0b.ico"));
+        assert!(!is_ours("favicon-16x16.3f9a1c0b.png"));
+        assert_eq!(content_hash(b"icon").len(), HASH_LEN);
+    }
+
+    #[test]
+    fn renames_staged_files_and_drops_older_fingerprints() {
+        let temp = TempDir::new("fingerprint");
+        let out_dir = temp.path();
+        fs::write(out_dir.join("favicon-32x32.00000000.png"), b"old").unwrap();
+        fs::write(out_dir.join("favicon-16x16.00000000.png"), b"other").unwrap();
+
+        let mut staging = Staging::new(&temp.path_str()).unwrap();
+        let staged = staging.dir().join("favicon-32x32.png");
+        fs::write(&staged, b"new").unwrap();
+        let refs = apply(&mut staging, &[staged], CacheBust::Name).unwrap();
+        staging.commit().unwrap();
+
+        let hashed = format!("favicon-32x32.{}.png", content_hash(b"new"));
+        assert_eq!(refs["favicon-32x32.png"], hashed);
+        assert!(out_dir.join(&hashed).exists());
+        assert!(!out_dir.join("favicon-32x32.00000000.png").exists());
+        // Only copies of the files this run wrote are touched
+        assert!(out_dir.join("favicon-16x16.00000000.png").exists());
+    }
+
+    #[test]
+    fn query_mode_keeps_names_and_adds_the_hash() {
+        let temp = TempDir::new("fingerprint");
+        let out_dir = temp.path();
+        fs::write(out_dir.join("favicon-32x32.00000000.png"), b"old").unwrap();
+
+        let mut staging = Staging::new(&temp.path_str()).unwrap();
+        let staged = staging.dir().join("favicon-32x32.png");
+        fs::write(&staged, b"new").unwrap();
+        let refs = apply(&mut staging, &[staged], CacheBust::Query).unwrap();
+        staging.commit().unwrap();
+
+        let expected = format!("favicon-32x32.png?v={}", content_hash(b"new"));
+        assert_eq!(refs["favicon-32x32.png"], expected);
+        assert!(out_dir.join("favicon-32x32.png").exists());
+        assert!(!out_dir.join("favicon-32x32.00000000.png").exists());
+    }
+}
@@ -0,0 +1,180 @@
+//! Making sources square: trim uniform margins, then pad, crop or stretch.
+
+use crate::cli::Fit;
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+/// How far a channel may stray from the margin color and still count as margin
+const TRIM_TOLERANCE: u8 = 8;
+
+/// Trims the margins if asked, then makes `image` square as `fit` says.
+///
+/// Returns a warning when the (trimmed) source isn't square, since every mode changes it.
+pub fn frame(image: DynamicImage, fit: Fit, trim: bool) -> (DynamicImage, Option<String>) {
+    let image = if trim { trim_margins(image) } else { image };
+    let (width, height) = (image.width(), image.height());
+    if width == height {
+        return (image, None);
+    }
+
+    let side = width.max(height);
+    let (framed, how) = match fit {
+        Fit::Contain => {
+            let mut canvas = RgbaImage::new(side, side);
+            let x = (side - width) / 2;
+            let y = (side - height) / 2;
+            imageops::replace(&mut canvas, &image.to_rgba8(), x.into(), y.into());
+            (
+                DynamicImage::ImageRgba8(canvas),
+                "it was centered on a transparent square",
+            )
+        }
+        Fit::Cover => {
+            let side = width.min(height);
+            let x = (width - side) / 2;
+            let y = (height - side) / 2;
+            (
+                image.crop_imm(x, y, side, side),
+                "its longer side was cropped to a square",
+            )
+        }
+        // The resampler scales both axes independently
+        Fit::Stretch => (image, "it will be stretched to a square"),
+    };
+    let warning = format!("The source is {width}x{height}, not square; {how} (--fit {fit})");
+    (framed, Some(warning))
+}
+
+/// Side of the square [`frame`] makes of a `width`x`height` image, ignoring trimming.
+///
+/// For `stretch` it's the shorter side, the one the resampler scales the most.
+pub fn framed_side(width: f32, height: f32, fit: Fit) -> f32 {
+    match fit {
+        Fit::Contain => width.max(height),
+        Fit::Cover | Fit::Stretch => width.min(height),
+    }
+}
+
+/// Crops away margins that match the top-left pixel: fully transparent ones, or a solid color
+/// within [`TRIM_TOLERANCE`]. An image that is all margin is returned as is.
+fn trim_margins(image: DynamicImage) -> DynamicImage {
+    let rgba = image.to_rgba8();
+    let background = *rgba.get_pixel(0, 0);
+    let is_margin = |pixel: &Rgba<u8>| {
+        if background[3] == 0 {
+            pixel[3] == 0
+        } else {
+            pixel
+                .0
+                .iter()
+                .zip(background.0)
+                .all(|(&a, b)| a.abs_diff(b) <= TRIM_TOLERANCE)
+        }
+    };
+
+    let (width, height) = rgba.dimensions();
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in rgba.enumerate_pixels() {
+        if !is_margin(pixel) {
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+    }
+    match bounds {
+        Some((left, top, right, bottom))
+            if (left, top, right, bottom) != (0, 0, width - 1, height - 1) =>
+        {
+            image.crop_imm(left, top, right - left + 1, bottom - top + 1)
+        }
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    /// A `width`x`height` image of `background` with a red box at `x..x+w`, `y..y+h`
+    fn boxed(size: (u32, u32), rect: (u32, u32, u32, u32), background: Rgba<u8>) -> DynamicImage {
+        let (x, y, w, h) = rect;
+        let image = RgbaImage::from_fn(size.0, size.1, |px, py| {
+            let inside = (x..x + w).contains(&px) && (y..y + h).contains(&py);
+            if inside {
+                RED
+            } else {
+                background
+            }
+        });
+        DynamicImage::ImageRgba8(image)
+    }
+
+    #[test]
+    fn leaves_square_sources_alone() {
+        let image = boxed((4, 4), (1, 1, 2, 2), CLEAR);
+        let (framed, warning) = frame(image.clone(), Fit::Cover, false);
+        assert_eq!(framed, image);
+        assert_eq!(warning, None);
+    }
+
+    #[test]
+    fn contain_centers_on_a_transparent_square() {
+        let (framed, warning) = frame(boxed((4, 2), (0, 0, 4, 2), RED), Fit::Contain, false);
+        let framed = framed.to_rgba8();
+        assert_eq!(framed.dimensions(), (4, 4));
+        assert_eq!(*framed.get_pixel(0, 0), CLEAR);
+        assert_eq!(*framed.get_pixel(0, 1), RED);
+        assert_eq!(*framed.get_pixel(3, 2), RED);
+        assert_eq!(*framed.get_pixel(3, 3), CLEAR);
+        assert!(warning.unwrap().contains("4x2"));
+    }
+
+    #[test]
+    fn cover_crops_the_middle_of_the_longer_side() {
+        // Only columns 2 and 3 of the 6x2 source are red
+        let (framed, _) = frame(boxed((6, 2), (2, 0, 2, 2), WHITE), Fit::Cover, false);
+        assert_eq!(
+            framed.to_rgba8(),
+            boxed((2, 2), (0, 0, 2, 2), WHITE).to_rgba8()
+        );
+    }
+
+    #[test]
+    fn stretch_keeps_the_source_for_the_resampler() {
+        let image = boxed((6, 2), (0, 0, 1, 1), WHITE);
+        let (framed, warning) = frame(image.clone(), Fit::Stretch, false);
+        assert_eq!(framed, image);
+        assert!(warning.is_some());
+        assert_eq!(framed_side(6.0, 2.0, Fit::Stretch), 2.0);
+        assert_eq!(framed_side(6.0, 2.0, Fit::Cover), 2.0);
+        assert_eq!(framed_side(6.0, 2.0, Fit::Contain), 6.0);
+    }
+
+    #[test]
+    fn trims_transparent_and_solid_margins_before_framing() {
+        let (framed, warning) = frame(boxed((10, 8), (3, 2, 4, 4), CLEAR), Fit::Contain, true);
+        assert_eq!(framed.dimensions(), (4, 4));
+        assert_eq!(warning, None);
+
+        // Near-white counts as the white margin, within the tolerance
+        let mut image = boxed((10, 10), (2, 3, 5, 3), WHITE).to_rgba8();
+        image.put_pixel(9, 9, Rgba([250, 250, 250, 255]));
+        let (framed, _) = frame(DynamicImage::ImageRgba8(image), Fit::Contain, true);
+        assert_eq!(framed.dimensions(), (5, 5));
+        assert_eq!(*framed.to_rgba8().get_pixel(0, 0), CLEAR);
+        assert_eq!(*framed.to_rgba8().get_pixel(0, 1), RED);
+    }
+
+    #[test]
+    fn keeps_an_image_that_is_all_margin() {
+        let image = boxed((6, 6), (0, 0, 0, 0), WHITE);
+        assert_eq!(trim_margins(image.clone()), image);
+    }
+}
@@ -0,0 +1,888 @@
+//! The `generate` pipeline: load the source, render every output, update the manifest.
+
+use crate::cli::{CacheBust, Fit};
+use crate::color;
+use crate::error::{FavisError, Result};
+use crate::fingerprint::{self, sha256_hex, AssetRefs};
+use crate::fit;
+use crate::icon_sizes::{self, IconPriority};
+use crate::img;
+use crate::legibility;
+use crate::limits::Limits;
+use crate::lock::{inputs_hash, LockFile, LockedOutput, OutputState, LOCK_FILE};
+use crate::manifest::{self, ManifestMetadata, MANIFEST_FILE};
+use crate::plan::{Plan, PlanAction};
+use crate::preview::{self, PREVIEW_FILE};
+use crate::resample::{self, Pixels, Resampling};
+use crate::stage::Staging;
+use crate::stdio;
+use crate::svg::{self, PixmapExt, SvgOptions};
+use image::DynamicImage;
+use indicatif::ProgressBar;
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{atomic::AtomicBool, Arc};
+
+/// Smallest side SVG sources are rendered at, since `img::process` refuses smaller images
+const MIN_RENDER_SIZE: u32 = 64;
+
+/// Fully resolved options for one generation run
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    /// Path to the source SVG or PNG
+    pub source: String,
+    /// Output directory
+    pub output: String,
+    /// Which icon sizes to produce
+    pub priority: IconPriority,
+    /// Write or update `manifest.webmanifest`
+    pub manifest: bool,
+    /// Allow raster sources
+    pub raster_ok: bool,
+    /// Manifest metadata; `theme_color` may be `auto`
+    pub metadata: ManifestMetadata,
+    /// Fingerprint outputs by renaming them or adding query strings
+    pub cache_bust: Option<CacheBust>,
+    /// How a non-square source is made square
+    pub fit: Fit,
+    /// Trim transparent or solid margins before fitting
+    pub trim: bool,
+    /// Resampling filters and sharpening
+    pub resampling: Resampling,
+    /// Fonts, stylesheet and units for SVG sources
+    pub svg: SvgOptions,
+    /// How large a source may be decoded or rendered
+    pub limits: Limits,
+    /// Mark PNG outputs as sRGB
+    pub srgb_chunk: bool,
+    /// Write an HTML contact sheet of the icons
+    pub preview: bool,
+    /// Ignore the lock file and regenerate everything
+    pub force: bool,
+}
+
+impl GenerateOptions {
+    /// Options that change rendered pixels or file names; part of every output's input hash
+    fn render_settings(&self, render_size: u32) -> serde_json::Value {
+        serde_json::json!({
+            "render_size": render_size,
+            "cache_bust": self.cache_bust,
+            "fit": self.fit,
+            "trim": self.trim,
+            "resampling": self.resampling,
+            "resampler": resample::RESAMPLER_VERSION,
+            "srgb_chunk": self.srgb_chunk,
+            "svg": self.svg,
+        })
+    }
+
+    /// SVG options with linked files resolved next to the source
+    fn svg_options(&self) -> SvgOptions {
+        // Relative `<image href>`s point next to the SVG file (or the working directory)
+        let resources_dir = match Path::new(&self.source).parent() {
+            Some(dir) if !stdio::is_stdio(&self.source) => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        SvgOptions {
+            resources_dir: Some(resources_dir),
+            ..self.svg.clone()
+        }
+    }
+
+    /// All options, recorded in the lock file for reference
+    fn snapshot(&self) -> serde_json::Value {
+        serde_json::json!({
+            "coverage": self.priority,
+            "manifest": self.manifest,
+            "metadata": self.metadata,
+            "cache_bust": self.cache_bust,
+            "fit": self.fit,
+            "trim": self.trim,
+            "resampling": self.resampling,
+            "srgb_chunk": self.srgb_chunk,
+        })
+    }
+}
+
+/// Source image kinds favis accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceKind {
+    Svg,
+    Png,
+}
+
+/// Reads the source file, or stdin when the source is `-`
+fn read_source(source: &str) -> Result<Vec<u8>> {
+    if stdio::is_stdio(source) {
+        return stdio::read_stdin("an SVG or PNG image");
+    }
+
+    // Validate source file exists
+    if !std::path::Path::new(source).exists() {
+        return Err(FavisError::file_not_found(source));
+    }
+    fs::read(source).map_err(|err| FavisError::read_failed(source, err))
+}
+
+/// Works out the source format and checks it is supported
+fn detect_source(options: &GenerateOptions, data: &[u8]) -> Result<SourceKind> {
+    // Check file extension to determine format
+    // Primary focus: SVG (vector graphics)
+    // Secondary support: PNG (raster, with quality warnings)
+    let source_lower = options.source.to_lowercase();
+    let kind = if stdio::is_stdio(&options.source) {
+        sniff_source(data)?
+    } else if source_lower.ends_with(".svg") {
+        SourceKind::Svg
+    } else if source_lower.ends_with(".png") {
+        SourceKind::Png
+    } else {
+        return Err(FavisError::invalid_format(
+            "Oops! That file format isn't supported.",
+        ));
+    };
+
+    // Check if using PNG (raster) and require explicit approval
+    if kind == SourceKind::Png && !options.raster_ok {
+        return Err(FavisError::invalid_format(
+            "PNG detected! You'll need the --raster-ok flag to continue.",
+        ));
+    }
+
+    Ok(kind)
+}
+
+/// Tells SVG from raster data by content, for sources without a file name
+fn sniff_source(data: &[u8]) -> Result<SourceKind> {
+    if image::guess_format(data).is_ok() {
+        return Ok(SourceKind::Png);
+    }
+    // SVG may start with a BOM, an XML declaration, a doctype or comments
+    let head = String::from_utf8_lossy(&data[..data.len().min(4096)]);
+    let text = head.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with('<') && text.contains("<svg") {
+        return Ok(SourceKind::Svg);
+    }
+    Err(FavisError::invalid_format(
+        "Oops! The data on stdin isn't an image format that's supported.",
+    ))
+}
+
+/// Decodes the source and makes it square, plus warnings about a color profile that could
+/// not be honored or a non-square source.
+///
+/// SVG sources are rendered so the square is `render_size` pixels.
+fn load_source(
+    data: &[u8],
+    options: &GenerateOptions,
+    kind: SourceKind,
+    render_size: u32,
+    spinner: &ProgressBar,
+) -> Result<(DynamicImage, Vec<String>)> {
+    let (image, profile_warning) = match kind {
+        SourceKind::Svg => return load_svg(data, options, render_size, spinner),
+        SourceKind::Png => img::load_raster(
+            data,
+            stdio::label(&options.source),
+            &options.limits,
+            Some(spinner),
+        )?,
+    };
+    // Padding to a square grows the image that every icon is resampled from
+    if options.fit == Fit::Contain {
+        let side = image.width().max(image.height());
+        options
+            .limits
+            .check("The source padded to a square", side, side)?;
+    }
+
+    if options.trim {
+        spinner.set_message(format!("{}", "Trimming margins...".cyan().bold()));
+    }
+    let (image, fit_warning) = fit::frame(image, options.fit, options.trim);
+    Ok((
+        image,
+        profile_warning.into_iter().chain(fit_warning).collect(),
+    ))
+}
+
+/// Renders an SVG source and makes it square, `render_size` pixels on each side
+fn load_svg(
+    data: &[u8],
+    options: &GenerateOptions,
+    render_size: u32,
+    spinner: &ProgressBar,
+) -> Result<(DynamicImage, Vec<String>)> {
+    spinner.set_message(format!("{}", "Rendering SVG to bitmap...".cyan().bold()));
+    let svg_options = options.svg_options();
+    let render = |size: u32| -> Result<(DynamicImage, Option<String>)> {
+        let pixmap = svg::render_svg_source(
+            data,
+            &svg_options,
+            size,
+            options.fit,
+            &options.limits,
+            Some(spinner),
+        )?;
+        spinner.set_message(format!("{}", "Converting to image format...".cyan().bold()));
+        let image = pixmap.to_dynamic_image()?;
+        if options.trim {
+            spinner.set_message(format!("{}", "Trimming margins...".cyan().bold()));
+        }
+        Ok(fit::frame(image, options.fit, options.trim))
+    };
+
+    let (mut image, mut fit_warning) = render(render_size)?;
+    // Trimming shrinks the artwork; render it larger so the trimmed square fills `render_size`
+    if image.width() < render_size {
+        let size = render_size as u64 * render_size as u64 / image.width().max(1) as u64;
+        // The first render is still usable if the larger one is over the limits
+        if let Ok(larger) = render(size.try_into().unwrap_or(u32::MAX)) {
+            (image, fit_warning) = larger;
+        }
+    }
+    Ok((image, fit_warning.into_iter().collect()))
+}
+
+/// An output the run is expected to produce, and whether it needs regenerating
+struct PlannedOutput {
+    /// Plain file name, e.g. `favicon-32x32.png`
+    name: String,
+    /// Icon sizes stored in the file
+    sizes: Vec<u32>,
+    /// Hash of the inputs that determine this output
+    inputs: String,
+    state: OutputState,
+}
+
+impl PlannedOutput {
+    fn needs_render(&self) -> bool {
+        self.state != OutputState::UpToDate
+    }
+}
+
+/// Whether a run wrote a file or left it alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Written,
+    Unchanged,
+}
+
+/// One output file as it is on disk after a run
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputFile {
+    /// Path including the output directory
+    pub path: String,
+    /// Icon sizes stored in the file; empty for the manifest
+    pub sizes: Vec<u32>,
+    pub bytes: u64,
+    pub sha256: String,
+    pub status: FileStatus,
+}
+
+/// What a generation run did
+#[derive(Debug, Default, Serialize)]
+pub struct GenerateReport {
+    pub output_dir: String,
+    /// Archive the files were packed into, with `--archive`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive: Option<String>,
+    pub files: Vec<OutputFile>,
+    pub warnings: Vec<String>,
+}
+
+impl GenerateReport {
+    /// Number of files with the given status
+    pub fn count(&self, status: FileStatus) -> usize {
+        self.files.iter().filter(|f| f.status == status).count()
+    }
+
+    /// Records an output; `dir` is where the file is right now (staging or the output dir)
+    fn record(&mut self, dir: &Path, locked: &LockedOutput, sizes: &[u32], status: FileStatus) {
+        let path = Path::new(&self.output_dir).join(&locked.file);
+        self.files.push(OutputFile {
+            bytes: fs::metadata(dir.join(&locked.file))
+                .map(|m| m.len())
+                .unwrap_or(0),
+            path: path.to_string_lossy().into_owned(),
+            sizes: sizes.to_vec(),
+            sha256: locked.hash.clone(),
+            status,
+        });
+    }
+}
+
+/// Everything known about a run before anything is rendered
+struct Prepared {
+    kind: SourceKind,
+    data: Vec<u8>,
+    source_hash: String,
+    /// Lock file from the previous run, unless `force` is set
+    previous: Option<LockFile>,
+    /// Every output the lock on disk records, even with `force`, so ones no longer
+    /// generated can be removed
+    recorded: Vec<LockedOutput>,
+    png_sizes: Vec<u32>,
+    ico_sizes: Vec<u32>,
+    /// Side SVG sources are rendered at: the largest output size
+    render_size: u32,
+    planned: Vec<PlannedOutput>,
+}
+
+/// Validates the options, reads the source and compares the expected outputs with the lock
+fn prepare(options: &GenerateOptions, spinner: &ProgressBar) -> Result<Prepared> {
+    options.metadata.validate()?;
+    options.svg.validate()?;
+    options.limits.validate()?;
+    let data = read_source(&options.source)?;
+    let kind = detect_source(options, &data)?;
+
+    spinner.set_message(format!(
+        "{} {}",
+        "Processing source file:".cyan().bold(),
+        stdio::label(&options.source).yellow()
+    ));
+
+    // Validate SVG data
+    if kind == SourceKind::Svg && data.is_empty() {
+        return Err(FavisError::invalid_svg("SVG file is empty"));
+    }
+
+    // Get the appropriate sizes based on priority
+    let png_sizes = icon_sizes::get_png_sizes(options.priority);
+    let ico_sizes = icon_sizes::get_ico_sizes(options.priority);
+    let render_size = png_sizes
+        .iter()
+        .chain(&ico_sizes)
+        .copied()
+        .max()
+        .unwrap_or_default()
+        .max(MIN_RENDER_SIZE);
+
+    // Work out which outputs are already up to date
+    let version = env!("CARGO_PKG_VERSION");
+    let source_hash = sha256_hex(&data);
+    let settings = options.render_settings(render_size).to_string();
+    // Fonts and linked images change the pixels as much as the SVG itself does
+    let linked = match kind {
+        SourceKind::Svg => linked_files_hash(&svg::linked_files(&data, &options.svg_options())),
+        SourceKind::Png => String::new(),
+    };
+    // With `force` a lock that can't be read is ignored rather than an error
+    let lock = if options.force {
+        LockFile::read(&options.output).unwrap_or(None)
+    } else {
+        LockFile::read(&options.output)?
+    };
+    let recorded: Vec<LockedOutput> = lock
+        .iter()
+        .flat_map(|lock| lock.outputs.values().cloned())
+        .collect();
+    let previous = lock.filter(|_| !options.force);
+    let state_of = |name: &str, inputs: &str| match &previous {
+        Some(lock) => lock.state_of(&options.output, name, inputs),
+        None => OutputState::Missing,
+    };
+
+    let mut planned: Vec<PlannedOutput> = png_sizes
+        .iter()
+        .map(|&size| {
+            let name = format!("favicon-{size}x{size}.png");
+            let inputs = inputs_hash(&[version, &source_hash, &settings, &linked, &name]);
+            let state = state_of(&name, &inputs);
+            PlannedOutput {
+                name,
+                sizes: vec![size],
+                inputs,
+                state,
+            }
+        })
+        .collect();
+    if !ico_sizes.is_empty() {
+        let name = "favicon.ico".to_string();
+        let ico_key = format!("{ico_sizes:?}");
+        let inputs = inputs_hash(&[version, &source_hash, &settings, &linked, &name, &ico_key]);
+        let state = state_of(&name, &inputs);
+        planned.push(PlannedOutput {
+            name,
+            sizes: ico_sizes.clone(),
+            inputs,
+            state,
+        });
+    }
+
+    Ok(Prepared {
+        kind,
+        data,
+        source_hash,
+        previous,
+        recorded,
+        png_sizes,
+        ico_sizes,
+        render_size,
+        planned,
+    })
+}
+
+/// Whether the run writes a manifest; metadata implies one, since it has nowhere else to go
+fn wants_manifest(options: &GenerateOptions) -> bool {
+    options.manifest || !options.metadata.is_empty()
+}
+
+/// Input hash of the manifest, given the outputs it references
+fn manifest_inputs(
+    options: &GenerateOptions,
+    outputs: &BTreeMap<String, LockedOutput>,
+    source_hash: &str,
+) -> Result<String> {
+    // An auto theme color depends on the source pixels too
+    let version = env!("CARGO_PKG_VERSION");
+    let metadata_json = serde_json::to_string(&options.metadata)?;
+    let refs_json = serde_json::to_string(&outputs.values().collect::<Vec<_>>())?;
+    let priority = format!("{:?}", options.priority);
+    let mut parts = vec![version, &metadata_json, &refs_json, &priority];
+    if options.metadata.wants_auto_theme_color() {
+        parts.push(source_hash);
+    }
+    Ok(inputs_hash(&parts))
+}
+
+/// Replaces an `auto` theme color with the dominant color of the source image
+fn resolve_metadata(
+    options: &GenerateOptions,
+    image: Option<DynamicImage>,
+    prepared: &Prepared,
+    spinner: &ProgressBar,
+) -> Result<ManifestMetadata> {
+    let mut metadata = options.metadata.clone().normalize_colors()?;
+    if metadata.wants_auto_theme_color() {
+        let image = match image {
+            Some(image) => image,
+            // Any warnings were reported by the run that rendered the icons
+            None => {
+                load_source(
+                    &prepared.data,
+                    options,
+                    prepared.kind,
+                    prepared.render_size,
+                    spinner,
+                )?
+                .0
+            }
+        };
+        spinner.set_message(format!(
+            "{}",
+            "Picking theme color from source image...".cyan().bold()
+        ));
+        let picked = color::dominant_color(&image).ok_or_else(|| {
+            FavisError::processing_error(
+                "Cannot pick a theme color: the image has no visible pixels",
+            )
+        })?;
+        metadata.theme_color = Some(picked);
+    }
+    Ok(metadata)
+}
+
+/// Outputs in `recorded` that are still on disk but whose file isn't in `kept`, each with
+/// whether it was edited since favis wrote it
+fn dropped_outputs<'a>(
+    out_dir: &Path,
+    recorded: &'a [LockedOutput],
+    kept: &[&str],
+) -> Vec<(&'a LockedOutput, bool)> {
+    recorded
+        .iter()
+        .filter(|locked| !kept.contains(&locked.file.as_str()))
+        .filter_map(|locked| {
+            let bytes = fs::read(out_dir.join(&locked.file)).ok()?;
+            Some((locked, sha256_hex(&bytes) != locked.hash))
+        })
+        .collect()
+}
+
+/// Describes what `run` would do, without rendering or writing any file
+pub fn plan(options: &GenerateOptions, spinner: &ProgressBar) -> Result<Plan> {
+    let prepared = prepare(options, spinner)?;
+    let out_dir = Path::new(&options.output);
+    let mut plan = Plan::default();
+    let mut asset_refs = AssetRefs::new();
+
+    for output in &prepared.planned {
+        let locked = prepared
+            .previous
+            .as_ref()
+            .and_then(|l| l.outputs.get(&output.name));
+        let sizes: Vec<String> = output.sizes.iter().map(|s| format!("{s}x{s}")).collect();
+        let mut note = sizes.join(", ");
+
+        let (file, action) = match (&output.state, locked) {
+            (OutputState::UpToDate, Some(locked)) => {
+                asset_refs.insert(output.name.clone(), locked.reference.clone());
+                (locked.file.clone(), PlanAction::Unchanged)
+            }
+            (OutputState::Modified, _) => {
+                note.push_str("; edited by hand");
+                (output.name.clone(), PlanAction::Conflict)
+            }
+            _ => {
+                let reference = match options.cache_bust {
+                    Some(mode) => fingerprint::placeholder_reference(&output.name, mode),
+                    None => output.name.clone(),
+                };
+                // In `name` mode the file on disk is the fingerprinted name
+                let file = match options.cache_bust {
+                    Some(CacheBust::Name) => reference.clone(),
+                    _ => output.name.clone(),
+                };
+                asset_refs.insert(output.name.clone(), reference);
+                let action = PlanAction::for_path(&out_dir.join(&file));
+                (file, action)
+            }
+        };
+        plan.push(file, action, Some(note));
+    }
+
+    if wants_manifest(options) {
+        let all_unchanged = plan
+            .entries
+            .iter()
+            .all(|e| e.action == PlanAction::Unchanged);
+        let locked = prepared
+            .previous
+            .as_ref()
+            .and_then(|l| l.outputs.get(MANIFEST_FILE));
+        let unchanged = match (all_unchanged, locked, &prepared.previous) {
+            (true, Some(locked), Some(previous)) => {
+                // Mirror the lock entries `run` would reference from the manifest
+                let outputs: BTreeMap<String, LockedOutput> = prepared
+                    .planned
+                    .iter()
+                    .filter_map(|p| Some((p.name.clone(), previous.outputs.get(&p.name)?.clone())))
+                    .collect();
+                let inputs = manifest_inputs(options, &outputs, &prepared.source_hash)?;
+                locked.inputs == inputs && out_dir.join(&locked.file).exists()
+            }
+            _ => false,
+        };
+
+        if unchanged {
+            plan.push(MANIFEST_FILE, PlanAction::Unchanged, None);
+        } else {
+            let metadata = resolve_metadata(options, None, &prepared, spinner)?;
+            plan.manifest_changes =
+                manifest::diff_manifest(&options.output, options.priority, &metadata, &asset_refs)?;
+            let path = out_dir.join(MANIFEST_FILE);
+            if plan.manifest_changes.is_empty() && path.exists() {
+                plan.push(MANIFEST_FILE, PlanAction::Unchanged, None);
+            } else {
+                let note = format!("{} field change(s)", plan.manifest_changes.len());
+                plan.push(MANIFEST_FILE, PlanAction::for_path(&path), Some(note));
+            }
+        }
+    }
+
+    if options.preview {
+        let path = out_dir.join(PREVIEW_FILE);
+        let note = "contact sheet, rewritten if any icon changes".to_string();
+        plan.push(PREVIEW_FILE, PlanAction::for_path(&path), Some(note));
+    }
+
+    let kept: Vec<&str> = plan.entries.iter().map(|e| e.file.as_str()).collect();
+    let removed: Vec<&str> = dropped_outputs(out_dir, &prepared.recorded, &kept)
+        .into_iter()
+        .filter(|(_, edited)| !edited)
+        .map(|(locked, _)| locked.file.as_str())
+        .collect();
+    if !removed.is_empty() {
+        plan.notes.push(format!(
+            "No longer generated, would be removed: {}",
+            removed.join(", ")
+        ));
+    }
+
+    // The lock only changes when some output does
+    let lock_path = out_dir.join(LOCK_FILE);
+    let all_unchanged = plan
+        .entries
+        .iter()
+        .all(|e| e.action == PlanAction::Unchanged);
+    let lock_action = if all_unchanged && lock_path.exists() {
+        PlanAction::Unchanged
+    } else {
+        PlanAction::for_path(&lock_path)
+    };
+    plan.push(LOCK_FILE, lock_action, None);
+    Ok(plan)
+}
+
+/// Runs the whole generation pipeline for one source
+///
+/// Outputs recorded in `favis.lock` with matching inputs are skipped; hand-edited outputs
+/// abort the run unless `force` is set.
+pub fn run(
+    options: &GenerateOptions,
+    spinner: &ProgressBar,
+    cancelled: Arc<AtomicBool>,
+) -> Result<GenerateReport> {
+    let prepared = prepare(options, spinner)?;
+    let kind = prepared.kind;
+    let previous = &prepared.previous;
+
+    let mut report = GenerateReport {
+        output_dir: options.output.clone(),
+        ..Default::default()
+    };
+
+    // Show warning for PNG images if proceeding
+    if kind == SourceKind::Png {
+        let warning = "PNG raster image quality may be poor at larger sizes";
+        report.warnings.push(warning.to_string());
+        if !spinner.is_hidden() {
+            spinner.set_message(format!("{} {warning}", "Warning:".yellow().bold()));
+            std::thread::sleep(std::time::Duration::from_millis(1500)); // Show warning briefly
+        }
+    }
+
+    // Never silently overwrite files someone edited by hand
+    let modified: Vec<String> = prepared
+        .planned
+        .iter()
+        .filter(|p| p.state == OutputState::Modified)
+        .map(|p| p.name.clone())
+        .collect();
+    if !modified.is_empty() {
+        return Err(FavisError::outputs_modified(&modified));
+    }
+
+    let render_sizes: Vec<u32> = prepared
+        .png_sizes
+        .iter()
+        .zip(&prepared.planned)
+        .filter(|(_, p)| p.needs_render())
+        .map(|(size, _)| *size)
+        .collect();
+    let render_ico = prepared
+        .planned
+        .iter()
+        .any(|p| p.name == "favicon.ico" && p.needs_render());
+
+    // Everything is written to a staging directory and moved into place at the end, so a
+    // failure or Ctrl+C never leaves a mix of old and new files behind
+    let mut staging = Staging::new(&options.output)?;
+    let out_dir = Path::new(&options.output);
+    let mut source_image: Option<DynamicImage> = None;
+    let mut outputs = BTreeMap::new();
+
+    if render_sizes.is_empty() && !render_ico {
+        spinner.set_message(format!(
+            "{}",
+            "Favicons are up to date, skipping rendering".cyan().bold()
+        ));
+    } else {
+        let (image, warnings) =
+            load_source(&prepared.data, options, kind, prepared.render_size, spinner)?;
+        report.warnings.extend(warnings);
+        let image = source_image.insert(image);
+        // Every size, and the legibility check, resamples from this one conversion
+        let pixels = Pixels::from_dynamic(image);
+
+        spinner.set_message(format!(
+            "{}",
+            "Checking legibility at small sizes...".cyan().bold()
+        ));
+        report
+            .warnings
+            .extend(legibility::check(&pixels, &options.resampling));
+
+        let ico_render_sizes: &[u32] = if render_ico { &prepared.ico_sizes } else { &[] };
+        let written = img::process(
+            &pixels,
+            &staging.dir_str(),
+            &render_sizes,
+            ico_render_sizes,
+            img::RenderOptions {
+                resampling: &options.resampling,
+                srgb_chunk: options.srgb_chunk,
+            },
+            Some(spinner),
+            cancelled,
+        )?;
+
+        let refs = match options.cache_bust {
+            Some(mode) => {
+                spinner.set_message(format!(
+                    "{}",
+                    "Fingerprinting files for cache busting...".cyan().bold()
+                ));
+                fingerprint::apply(&mut staging, &written, mode)?
+            }
+            None => written
+                .iter()
+                .filter_map(|path| path.file_name())
+                .map(|name| {
+                    let name = name.to_string_lossy().into_owned();
+                    (name.clone(), name)
+                })
+                .collect(),
+        };
+
+        for (name, reference) in refs {
+            // In `name` mode the reference is the fingerprinted file name
+            let file = match options.cache_bust {
+                Some(CacheBust::Name) => reference.clone(),
+                _ => name.clone(),
+            };
+            let hash = hash_file(staging.dir(), &file)?;
+            outputs.insert(name, (file, reference, hash));
+        }
+    }
+
+    let mut lock = LockFile {
+        favis_version: env!("CARGO_PKG_VERSION").to_string(),
+        source: options.source.clone(),
+        source_hash: prepared.source_hash.clone(),
+        options: options.snapshot(),
+        outputs: Default::default(),
+    };
+    for plan in &prepared.planned {
+        if let Some((file, reference, hash)) = outputs.remove(&plan.name) {
+            let locked = LockedOutput {
+                file,
+                reference,
+                hash,
+                inputs: plan.inputs.clone(),
+            };
+            report.record(staging.dir(), &locked, &plan.sizes, FileStatus::Written);
+            lock.outputs.insert(plan.name.clone(), locked);
+        } else if let Some(locked) = previous.as_ref().and_then(|l| l.outputs.get(&plan.name)) {
+            report.record(out_dir, locked, &plan.sizes, FileStatus::Unchanged);
+            lock.outputs.insert(plan.name.clone(), locked.clone());
+        }
+    }
+
+    if wants_manifest(options) {
+        let name = MANIFEST_FILE.to_string();
+        let asset_refs: AssetRefs = lock
+            .outputs
+            .iter()
+            .map(|(name, locked)| (name.clone(), locked.reference.clone()))
+            .collect();
+        let inputs = manifest_inputs(options, &lock.outputs, &prepared.source_hash)?;
+
+        // The manifest merges with hand edits, so only changed inputs matter here
+        let locked = previous.as_ref().and_then(|l| l.outputs.get(&name));
+        let unchanged =
+            locked.is_some_and(|l| l.inputs == inputs && out_dir.join(&l.file).exists());
+
+        if let (true, Some(locked)) = (unchanged, locked) {
+            report.record(out_dir, locked, &[], FileStatus::Unchanged);
+            lock.outputs.insert(name, locked.clone());
+        } else {
+            let metadata = resolve_metadata(options, source_image.take(), &prepared, spinner)?;
+            // Update a copy so hand-added fields survive and the original stays until commit
+            staging.copy_in(MANIFEST_FILE)?;
+            manifest::generate_manifest(
+                &staging.dir_str(),
+                options.priority,
+                &metadata,
+                &asset_refs,
+                Some(spinner),
+            )?;
+            let hash = hash_file(staging.dir(), &name)?;
+            let locked = LockedOutput {
+                file: name.clone(),
+                reference: name.clone(),
+                hash,
+                inputs,
+            };
+            report.record(staging.dir(), &locked, &[], FileStatus::Written);
+            lock.outputs.insert(name, locked);
+        }
+    }
+
+    // Outputs the last run wrote but this one doesn't, e.g. fingerprinted names after
+    // switching cache-bust modes; files edited since are left alone
+    let kept: Vec<&str> = lock.outputs.values().map(|l| l.file.as_str()).collect();
+    for (locked, edited) in dropped_outputs(out_dir, &prepared.recorded, &kept) {
+        if edited {
+            report.warnings.push(format!(
+                "{} is no longer generated but was edited by hand, so it was left in place",
+                locked.file
+            ));
+        } else {
+            staging.remove_on_commit(out_dir.join(&locked.file));
+        }
+    }
+
+    lock.write(&staging.dir_str())?;
+    staging.commit()?;
+
+    // The sheet shows the files as committed, so it's written last and isn't locked
+    if options.preview {
+        spinner.set_message(format!("{}", "Writing preview page...".cyan().bold()));
+        let html = preview::render(&report, options.metadata.name.as_deref())?;
+        let status = if preview::write(out_dir, &html)? {
+            FileStatus::Written
+        } else {
+            FileStatus::Unchanged
+        };
+        report.files.push(OutputFile {
+            path: out_dir.join(PREVIEW_FILE).to_string_lossy().into_owned(),
+            sizes: Vec::new(),
+            bytes: html.len() as u64,
+            sha256: sha256_hex(html.as_bytes()),
+            status,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Hash of the paths and contents of `files`; a missing file hashes differently from an empty one
+fn linked_files_hash(files: &[PathBuf]) -> String {
+    let parts: Vec<String> = files
+        .iter()
+        .map(|path| {
+            let contents =
+                fs::read(path).map_or_else(|_| "missing".to_string(), |bytes| sha256_hex(&bytes));
+            format!("{}\0{contents}", path.display())
+        })
+        .collect();
+    sha256_hex(parts.join("\0").as_bytes())
+}
+
+/// SHA-256 of a file in `dir`
+fn hash_file(dir: &Path, file: &str) -> Result<String> {
+    let path = dir.join(file);
+    let bytes = fs::read(&path).map_err(|err| {
+        FavisError::write_error(format!("Cannot read back {}", path.display())).with_source(err)
+    })?;
+    Ok(sha256_hex(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stage::TempDir;
+
+    #[test]
+    fn linked_files_hash_follows_their_contents() {
+        let dir = TempDir::new("linked");
+        let files = [dir.path().join("logo.png")];
+        let missing = linked_files_hash(&files);
+        fs::write(&files[0], "one").unwrap();
+        let one = linked_files_hash(&files);
+        fs::write(&files[0], "two").unwrap();
+        let two = linked_files_hash(&files);
+
+        assert_ne!(missing, one);
+        assert_ne!(one, two);
+        assert_eq!(two, linked_files_hash(&files));
+        assert_ne!(linked_files_hash(&[]), missing);
+    }
+}
@@ -0,0 +1,136 @@
+//! Embedded ICC profiles: convert raster sources to sRGB before they are resized.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+/// Converts `image` from its embedded ICC profile to sRGB.
+///
+/// When the profile can't be read or applied, the image is returned as it was, along with a
+/// warning explaining why its colors may be off.
+pub fn to_srgb(image: DynamicImage, icc: &[u8]) -> (DynamicImage, Option<String>) {
+    let warn = |reason: String| {
+        Some(format!(
+            "The source has an embedded color profile that {reason}; colors were left as they are and may look off"
+        ))
+    };
+    let profile = match ColorProfile::new_from_slice(icc) {
+        Ok(profile) => profile,
+        Err(e) => return (image, warn(format!("could not be read ({e})"))),
+    };
+
+    let converted = match profile.color_space {
+        DataColorSpace::Rgb if is_high_depth(&image) => convert_rgba16(&image, &profile),
+        DataColorSpace::Rgb => convert_rgba8(&image, &profile, Layout::Rgba),
+        DataColorSpace::Gray => convert_rgba8(&image, &profile, Layout::GrayAlpha),
+        other => Err(format!("uses an unsupported color space ({other:?})")),
+    };
+    match converted {
+        Ok(converted) => (converted, None),
+        Err(reason) => (image, warn(reason)),
+    }
+}
+
+/// Whether converting through 8 bits would throw away precision
+fn is_high_depth(image: &DynamicImage) -> bool {
+    !matches!(
+        image,
+        DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_)
+    )
+}
+
+fn convert_rgba8(
+    image: &DynamicImage,
+    profile: &ColorProfile,
+    layout: Layout,
+) -> std::result::Result<DynamicImage, String> {
+    let transform = profile
+        .create_transform_8bit(
+            layout,
+            &ColorProfile::new_srgb(),
+            Layout::Rgba,
+            TransformOptions::default(),
+        )
+        .map_err(|e| format!("could not be applied ({e})"))?;
+    let source = match layout {
+        Layout::GrayAlpha => image.to_luma_alpha8().into_raw(),
+        _ => image.to_rgba8().into_raw(),
+    };
+    let mut pixels = vec![0u8; image.width() as usize * image.height() as usize * 4];
+    transform
+        .transform(&source, &mut pixels)
+        .map_err(|e| format!("could not be applied ({e})"))?;
+    ImageBuffer::<Rgba<u8>, _>::from_raw(image.width(), image.height(), pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "produced an unexpected buffer size".to_string())
+}
+
+fn convert_rgba16(
+    image: &DynamicImage,
+    profile: &ColorProfile,
+) -> std::result::Result<DynamicImage, String> {
+    let transform = profile
+        .create_transform_16bit(
+            Layout::Rgba,
+            &ColorProfile::new_srgb(),
+            Layout::Rgba,
+            TransformOptions::default(),
+        )
+        .map_err(|e| format!("could not be applied ({e})"))?;
+    let source = image.to_rgba16().into_raw();
+    let mut pixels = vec![0u16; source.len()];
+    transform
+        .transform(&source, &mut pixels)
+        .map_err(|e| format!("could not be applied ({e})"))?;
+    ImageBuffer::<Rgba<u16>, _>::from_raw(image.width(), image.height(), pixels)
+        .map(DynamicImage::ImageRgba16)
+        .ok_or_else(|| "produced an unexpected buffer size".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn orange() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba([200, 100, 50, 255])))
+    }
+
+    fn pixel(image: &DynamicImage) -> [u8; 4] {
+        image.to_rgba8().get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn converts_display_p3_sources() {
+        let icc = ColorProfile::new_display_p3().encode().unwrap();
+        let (converted, warning) = to_srgb(orange(), &icc);
+
+        assert_eq!(warning, None);
+        let [r, g, b, a] = pixel(&converted);
+        // The same numbers mean a more saturated color in P3, so red goes up in sRGB
+        assert!(r > 200 && b < 50, "{:?}", [r, g, b, a]);
+        assert_eq!(a, 255);
+    }
+
+    #[test]
+    fn leaves_srgb_sources_alone() {
+        let icc = ColorProfile::new_srgb().encode().unwrap();
+        let (converted, warning) = to_srgb(orange(), &icc);
+
+        assert_eq!(warning, None);
+        let [r, g, b, a] = pixel(&converted);
+        for (got, want) in [(r, 200), (g, 100), (b, 50), (a, 255)] {
+            assert!(got.abs_diff(want) <= 1, "{:?}", [r, g, b, a]);
+        }
+    }
+
+    #[test]
+    fn warns_about_profiles_it_cannot_read() {
+        let (converted, warning) = to_srgb(orange(), b"not a profile");
+
+        assert!(warning.unwrap().contains("could not be read"));
+        assert_eq!(pixel(&converted), [200, 100, 50, 255]);
+    }
+}
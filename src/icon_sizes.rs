@@ -1,7 +1,10 @@
 //! Centralized icon size definitions with priority and purpose metadata.
 
+use serde::Serialize;
+
 /// Icon priority level.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum IconPriority {
     /// Level 1: Required sizes only (minimal set)
     Required = 1,
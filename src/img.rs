@@ -1,145 +1,186 @@
 //! Image processing for PNG and ICO outputs.
 
 use crate::error::{FavisError, Result};
+use crate::icc;
+use crate::limits::Limits;
+use crate::resample::{Pixels, Resampling};
 use ico::{IconDir, IconImage, ResourceType};
-use image::{imageops::FilterType, ImageEncoder};
+use image::{DynamicImage, ImageDecoder, ImageReader, RgbaImage};
 use indicatif::ProgressBar;
 use owo_colors::OwoColorize;
-use std::fs::{self, File};
-use std::io::BufWriter;
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-/// Tracks files created during processing for cleanup on interruption
-#[derive(Debug)]
-struct FileTracker {
-    files: Vec<PathBuf>,
-    cancelled: Arc<AtomicBool>,
-}
-
-impl FileTracker {
-    fn new_with_cancellation(cancelled: Arc<AtomicBool>) -> Self {
-        Self {
-            files: Vec::new(),
-            cancelled,
-        }
-    }
-
-    fn track(&mut self, path: PathBuf) {
-        self.files.push(path);
+/// Decodes a raster source image (PNG/JPEG/GIF) from its file contents.
+///
+/// Images with an embedded ICC profile are converted to sRGB; if that isn't possible, the
+/// image is returned unconverted together with a warning. Images over `limits` are refused
+/// before their pixels are decoded.
+pub fn load_raster(
+    data: &[u8],
+    src_path: &str,
+    limits: &Limits,
+    progress: Option<&ProgressBar>,
+) -> Result<(DynamicImage, Option<String>)> {
+    if let Some(pb) = progress {
+        pb.set_message(format!(
+            "{} {}",
+            "Loading source image:".cyan().bold(),
+            src_path.yellow()
+        ));
     }
 
-    fn is_cancelled(&self) -> bool {
-        self.cancelled.load(Ordering::Relaxed)
-    }
+    let cannot_open =
+        || FavisError::invalid_format(format!("Cannot decode image file: {src_path}"));
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|err| cannot_open().with_source(err))?
+        .into_decoder()
+        .map_err(|err| cannot_open().with_source(err))?;
+    let (width, height) = decoder.dimensions();
+    limits.check("The source image", width, height)?;
+    decoder.set_limits(limits.decoder_limits())?;
+    let icc = decoder.icc_profile().ok().flatten();
+    let image = DynamicImage::from_decoder(decoder).map_err(|err| match err {
+        image::ImageError::Limits(_) => FavisError::from(err),
+        _ => cannot_open().with_source(err),
+    })?;
 
-    fn cleanup(&self) {
-        for file in &self.files {
-            if file.exists() {
-                let _ = fs::remove_file(file); // Ignore errors during cleanup
+    match icc {
+        Some(icc) => {
+            if let Some(pb) = progress {
+                pb.set_message(format!("{}", "Converting colors to sRGB...".cyan().bold()));
             }
+            Ok(icc::to_srgb(image, &icc))
         }
+        None => Ok((image, None)),
     }
 }
 
-impl Drop for FileTracker {
-    fn drop(&mut self) {
-        if self.is_cancelled() {
-            self.cleanup();
-        }
-    }
+/// Decodes an image file of any supported format to RGBA, refusing images over `limits`
+/// before their pixels are decoded.
+///
+/// `name` identifies the file in errors.
+pub fn decode_rgba(data: &[u8], name: &str, limits: &Limits) -> Result<RgbaImage> {
+    let cannot_decode = || FavisError::invalid_format(format!("Cannot decode image file: {name}"));
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|err| cannot_decode().with_source(err))?
+        .into_decoder()
+        .map_err(|err| cannot_decode().with_source(err))?;
+    let (width, height) = decoder.dimensions();
+    limits.check(&format!("The image {name}"), width, height)?;
+    decoder
+        .set_limits(limits.decoder_limits())
+        .map_err(|err| cannot_decode().with_source(err))?;
+    let image =
+        DynamicImage::from_decoder(decoder).map_err(|err| cannot_decode().with_source(err))?;
+    Ok(image.into_rgba8())
 }
 
-/// Processes a source image (PNG/JPEG/GIF) and generates resized PNGs and an optional ICO.
-/// Includes graceful handling of user cancellation and cleanup of partial files.
+/// Decodes every image stored in an ICO file, in the order of its directory.
+///
+/// Entries over `limits` are refused before their pixels are decoded; `name` identifies
+/// the file in errors.
+pub fn decode_ico(data: &[u8], name: &str, limits: &Limits) -> Result<Vec<RgbaImage>> {
+    let cannot_decode = || FavisError::invalid_format(format!("Cannot read ICO file: {name}"));
+    let icon_dir =
+        IconDir::read(Cursor::new(data)).map_err(|err| cannot_decode().with_source(err))?;
+    icon_dir
+        .entries()
+        .iter()
+        .map(|entry| {
+            // The directory reports each entry's real size, read from its PNG or BMP header
+            limits.check(
+                &format!("An entry of {name}"),
+                entry.width(),
+                entry.height(),
+            )?;
+            let image = entry
+                .decode()
+                .map_err(|err| cannot_decode().with_source(err))?;
+            RgbaImage::from_raw(image.width(), image.height(), image.rgba_data().to_vec())
+                .ok_or_else(cannot_decode)
+        })
+        .collect()
+}
+
+/// How each icon is scaled and encoded
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions<'a> {
+    /// Filters and sharpening used to scale the image down
+    pub resampling: &'a Resampling,
+    /// Mark PNG outputs as sRGB with an `sRGB` chunk
+    pub srgb_chunk: bool,
+}
+
+/// Generates resized PNGs and an optional ICO from a decoded source image.
+/// Stops early when cancelled; callers write into a staging directory, so partial
+/// outputs never reach the real output directory.
 ///
 /// # Arguments
-/// * `src_path` - Path to the source image file.
-/// * `out_dir` - Directory inside which to save outputs.
+/// * `source` - Decoded source image, converted once for resampling.
+/// * `out_dir` - Directory inside which to save outputs (usually a staging directory).
 /// * `png_sizes` - List of square sizes (in px) to generate PNGs.
 /// * `ico_sizes` - List of sizes to include in the ICO; if empty, no ICO is generated.
+/// * `render` - Resampling and PNG encoding settings.
 /// * `progress` - Optional progress bar for user feedback.
 /// * `cancelled` - Shared cancellation flag for graceful interruption.
+///
+/// Returns the paths of all files written, in creation order.
 pub fn process(
-    src_path: &str,
+    source: &Pixels,
     out_dir: &str,
     png_sizes: &[u32],
     ico_sizes: &[u32],
+    render: RenderOptions,
     progress: Option<&ProgressBar>,
     cancelled: Arc<AtomicBool>,
-) -> Result<()> {
-    // Read and decode source
-    if let Some(pb) = progress {
-        pb.set_message(format!(
-            "{} {}",
-            "Loading source image:".cyan().bold(),
-            src_path.yellow()
-        ));
-    }
-
-    let img = image::open(src_path)
-        .map_err(|_| FavisError::file_not_found(format!("Cannot open image file: {src_path}")))?;
-
+) -> Result<Vec<PathBuf>> {
     // Check minimum image dimensions for quality
-    if img.width() < 64 || img.height() < 64 {
+    if source.width() < 64 || source.height() < 64 {
         return Err(FavisError::image_too_small(64));
     }
 
-    // Ensure output directory exists
-    fs::create_dir_all(out_dir).map_err(|_| {
-        FavisError::write_error(format!("Cannot create output directory: {out_dir}"))
-    })?;
-
-    let mut file_tracker = FileTracker::new_with_cancellation(cancelled);
+    let is_cancelled = || cancelled.load(Ordering::Relaxed);
+    let mut written = Vec::new();
 
     // Helper: Save resized PNG
     fn save_resized_png(
-        img: &image::DynamicImage,
+        source: &Pixels,
         size: u32,
         out_dir: &str,
-        file_tracker: &mut FileTracker,
-    ) -> Result<()> {
-        let mut resized = img.resize_exact(size, size, FilterType::Lanczos3);
-
-        // Clear edge artifacts by ensuring transparency or solid color
-        resized = resized.adjust_contrast(1.0); // Adjust contrast to minimize border artifacts
+        render: RenderOptions,
+    ) -> Result<PathBuf> {
+        let rgba = render.resampling.render(source, size);
 
         let mut out_path = PathBuf::from(out_dir);
         out_path.push(format!("favicon-{size}x{size}.png"));
 
-        file_tracker.track(out_path.clone());
-
-        let file = File::create(&out_path).map_err(|_| {
+        let file = File::create(&out_path).map_err(|err| {
             FavisError::write_error(format!("Cannot create PNG file: {}", out_path.display()))
+                .with_source(err)
+        })?;
+        write_png(file, &rgba, render.srgb_chunk).map_err(|err| {
+            FavisError::write_error(format!("Cannot write PNG file: {}", out_path.display()))
+                .with_source(err)
         })?;
 
-        let buf_writer = BufWriter::new(file);
-        let encoder = image::codecs::png::PngEncoder::new(buf_writer);
-        let rgba = resized.to_rgba8();
-
-        encoder
-            .write_image(
-                rgba.as_raw(),
-                rgba.width(),
-                rgba.height(),
-                image::ExtendedColorType::Rgba8,
-            )
-            .map_err(|_| FavisError::write_error("Cannot encode PNG image"))?;
-
-        Ok(())
+        Ok(out_path)
     }
 
     // Helper: Get RGBA for ICO
-    fn get_rgba_for_ico(img: &image::DynamicImage, size: u32) -> Vec<u8> {
-        let resized = img.resize_exact(size, size, FilterType::Lanczos3);
-        resized.to_rgba8().into_raw()
+    fn get_rgba_for_ico(source: &Pixels, size: u32, resampling: &Resampling) -> Vec<u8> {
+        resampling.render(source, size).into_raw()
     }
 
     // Generate PNGs
     for &size in png_sizes {
         // Check for cancellation before each PNG
-        if file_tracker.is_cancelled() {
+        if is_cancelled() {
             return Err(FavisError::user_cancelled());
         }
 
@@ -151,13 +192,13 @@ pub fn process(
                 size.to_string().yellow()
             ));
         }
-        save_resized_png(&img, size, out_dir, &mut file_tracker)?;
+        written.push(save_resized_png(source, size, out_dir, render)?);
     }
 
     // Generate ICO if requested
     if !ico_sizes.is_empty() {
         // Check for cancellation before ICO generation
-        if file_tracker.is_cancelled() {
+        if is_cancelled() {
             return Err(FavisError::user_cancelled());
         }
 
@@ -171,7 +212,7 @@ pub fn process(
         let mut icon_dir = IconDir::new(ResourceType::Icon);
         for &size in ico_sizes {
             // Check for cancellation during ICO size processing
-            if file_tracker.is_cancelled() {
+            if is_cancelled() {
                 return Err(FavisError::user_cancelled());
             }
 
@@ -183,11 +224,12 @@ pub fn process(
                     size.to_string().yellow()
                 ));
             }
-            let rgba = get_rgba_for_ico(&img, size);
+            let rgba = get_rgba_for_ico(source, size, render.resampling);
             let icon_image = IconImage::from_rgba_data(size, size, rgba);
             // encode_png returns Result<IconDirEntry, _>, so handle error and add entry
-            let entry = ico::IconDirEntry::encode(&icon_image).map_err(|_| {
+            let entry = ico::IconDirEntry::encode(&icon_image).map_err(|err| {
                 FavisError::processing_error(format!("Cannot encode {size}x{size} icon for ICO"))
+                    .with_source(err)
             })?;
             icon_dir.add_entry(entry);
         }
@@ -195,23 +237,148 @@ pub fn process(
         let mut ico_path = PathBuf::from(out_dir);
         ico_path.push("favicon.ico");
 
-        // Track ICO file for cleanup if cancelled
-        file_tracker.track(ico_path.clone());
-
         if let Some(pb) = progress {
             pb.set_message(format!("{}", "Writing favicon.ico file...".cyan().bold()));
         }
 
-        let mut file = BufWriter::new(File::create(&ico_path).map_err(|_| {
+        let file = File::create(&ico_path).map_err(|err| {
             FavisError::write_error(format!("Cannot create ICO file: {}", ico_path.display()))
-        })?);
+                .with_source(err)
+        })?;
+        write_ico(file, &icon_dir).map_err(|err| {
+            FavisError::write_error(format!("Cannot write ICO file: {}", ico_path.display()))
+                .with_source(err)
+        })?;
+        written.push(ico_path);
+    }
+
+    Ok(written)
+}
+
+/// Encodes `rgba` as a PNG into `out`.
+///
+/// The PNG is finished and the buffer flushed here, so a failed final write is reported
+/// instead of leaving a truncated file behind.
+fn write_png<W: Write>(out: W, rgba: &RgbaImage, srgb_chunk: bool) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    let mut encoder = png::Encoder::new(&mut out, rgba.width(), rgba.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Default);
+    encoder.set_filter(png::FilterType::Sub);
+    encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+    if srgb_chunk {
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    }
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(rgba.as_raw())
+        .map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+    out.into_inner().map_err(|err| err.into_error())?.flush()
+}
+
+/// Writes `icon_dir` into `out`, flushing it so no write error goes unnoticed
+fn write_ico<W: Write>(out: W, icon_dir: &IconDir) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    icon_dir.write(&mut out)?;
+    out.into_inner().map_err(|err| err.into_error())?.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accepts `capacity` bytes, then fails every write; flushing fails if `fail_flush` is set
+    struct FailingWriter {
+        written: Vec<u8>,
+        capacity: usize,
+        fail_flush: bool,
+    }
+
+    impl FailingWriter {
+        fn new(capacity: usize, fail_flush: bool) -> Self {
+            Self {
+                written: Vec::new(),
+                capacity,
+                fail_flush,
+            }
+        }
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written.len() + buf.len() > self.capacity {
+                return Err(io::Error::other("disk full"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.fail_flush {
+                Err(io::Error::other("flush failed"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn sample_image() -> RgbaImage {
+        RgbaImage::from_fn(16, 16, |x, y| {
+            image::Rgba([x as u8 * 16, y as u8 * 16, 128, 255])
+        })
+    }
+
+    fn sample_icon_dir() -> IconDir {
+        let mut icon_dir = IconDir::new(ResourceType::Icon);
+        let icon = IconImage::from_rgba_data(16, 16, sample_image().into_raw());
+        icon_dir.add_entry(ico::IconDirEntry::encode(&icon).unwrap());
         icon_dir
-            .write(&mut file)
-            .map_err(|_| FavisError::write_error("Cannot write ICO file data"))?;
     }
 
-    // If we get here, processing completed successfully - don't cleanup files
-    std::mem::forget(file_tracker);
+    #[test]
+    fn png_round_trips() {
+        let mut out = Vec::new();
+        write_png(&mut out, &sample_image(), true).unwrap();
+        let decoded = image::load_from_memory(&out).unwrap().to_rgba8();
+        assert_eq!(decoded, sample_image());
+    }
+
+    #[test]
+    fn png_reports_a_failed_final_write() {
+        // Everything fits except the last bytes, which only reach the file when flushed
+        let mut full = Vec::new();
+        write_png(&mut full, &sample_image(), false).unwrap();
+        let mut out = FailingWriter::new(full.len() - 1, false);
+        assert!(write_png(&mut out, &sample_image(), false).is_err());
+    }
+
+    #[test]
+    fn png_reports_a_failed_flush() {
+        let mut out = FailingWriter::new(usize::MAX, true);
+        assert!(write_png(&mut out, &sample_image(), false).is_err());
+    }
+
+    #[test]
+    fn ico_reports_a_failed_final_write() {
+        let mut full = Vec::new();
+        write_ico(&mut full, &sample_icon_dir()).unwrap();
+        let mut out = FailingWriter::new(full.len() - 1, false);
+        assert!(write_ico(&mut out, &sample_icon_dir()).is_err());
 
-    Ok(())
+        let mut out = FailingWriter::new(usize::MAX, true);
+        assert!(write_ico(&mut out, &sample_icon_dir()).is_err());
+    }
+
+    #[test]
+    fn ico_round_trips() {
+        let mut out = Vec::new();
+        write_ico(&mut out, &sample_icon_dir()).unwrap();
+        assert_eq!(
+            decode_ico(&out, "favicon.ico", &Limits::default()).unwrap(),
+            vec![sample_image()]
+        );
+    }
 }
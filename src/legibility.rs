@@ -0,0 +1,212 @@
+//! Legibility checks on the smallest icons, reported as warnings.
+//!
+//! These are heuristics on the rendered 16px and 32px icons: they point at likely problems,
+//! not certain ones.
+
+use crate::resample::{Pixels, Resampling};
+use image::{Rgba, RgbaImage};
+
+/// Sizes browsers show in tabs, bookmarks and history
+const SIZES: [u32; 2] = [16, 32];
+/// Size the edge and margin checks look at
+const LAYOUT_SIZE: u32 = 32;
+
+/// Alpha from which a pixel counts as part of the artwork
+const VISIBLE_ALPHA: u8 = 26;
+/// Alpha below which a visible pixel is only partly covered
+const SOLID_ALPHA: u8 = 128;
+/// Share of partly covered pixels above which details are too thin
+const MAX_FAINT_SHARE: f32 = 0.4;
+
+/// Typical tab backgrounds: light, and dark mode
+const LIGHT_TAB: [u8; 3] = [0xff, 0xff, 0xff];
+const DARK_TAB: [u8; 3] = [0x35, 0x36, 0x3a];
+/// Contrast ratio a pixel needs against the tab to stand out (WCAG's minimum for graphics)
+const MIN_CONTRAST: f32 = 3.0;
+/// Share of the artwork that has to reach [`MIN_CONTRAST`]
+const MIN_CONTRASTING_SHARE: f32 = 0.2;
+
+/// Share of the border that may be covered before it counts as a full-bleed background
+const MAX_EDGE_SHARE: f32 = 0.5;
+/// Share of the icon's width or height the artwork should fill
+const MIN_FILL: f32 = 0.75;
+
+/// Renders the 16px and 32px icons from `source` and returns a warning per likely problem
+pub fn check(source: &Pixels, resampling: &Resampling) -> Vec<String> {
+    let icons: Vec<(u32, RgbaImage)> = SIZES
+        .iter()
+        .map(|&size| (size, resampling.render(source, size)))
+        .collect();
+    let failing = |test: &dyn Fn(&RgbaImage) -> bool| -> Option<String> {
+        let sizes: Vec<String> = icons
+            .iter()
+            .filter(|(_, icon)| test(icon))
+            .map(|(size, _)| format!("{size}x{size}"))
+            .collect();
+        (!sizes.is_empty()).then(|| sizes.join(" and "))
+    };
+
+    let mut warnings = Vec::new();
+    if let Some(sizes) = failing(&|icon| faint_share(icon) > MAX_FAINT_SHARE) {
+        warnings.push(format!(
+            "Details thinner than a pixel look faint or blurry at {sizes}; thicken strokes or simplify the small icons"
+        ));
+    }
+    for (tab, background) in [("light", LIGHT_TAB), ("dark", DARK_TAB)] {
+        let low_contrast =
+            |icon: &RgbaImage| contrasting_share(icon, background) < MIN_CONTRASTING_SHARE;
+        if let Some(sizes) = failing(&low_contrast) {
+            warnings.push(format!(
+                "The icon has little contrast against {tab} browser tabs at {sizes} and may be hard to see"
+            ));
+        }
+    }
+
+    let (_, layout) = icons
+        .iter()
+        .find(|(size, _)| *size == LAYOUT_SIZE)
+        .expect("the layout size is rendered");
+    let edge = edge_share(layout);
+    if edge > 0.0 && edge < MAX_EDGE_SHARE {
+        warnings.push(format!(
+            "The artwork touches the edge of the {LAYOUT_SIZE}x{LAYOUT_SIZE} icon and may look clipped; leave a little padding"
+        ));
+    }
+    if let Some(fill) = fill(layout).filter(|&fill| fill < MIN_FILL) {
+        warnings.push(format!(
+            "The artwork only fills {:.0}% of the {LAYOUT_SIZE}x{LAYOUT_SIZE} icon and will look small in tabs; try --trim",
+            fill * 100.0
+        ));
+    }
+    warnings
+}
+
+fn is_visible(pixel: &Rgba<u8>) -> bool {
+    pixel[3] >= VISIBLE_ALPHA
+}
+
+/// Share of the visible pixels that are only partly covered
+fn faint_share(icon: &RgbaImage) -> f32 {
+    let visible = icon.pixels().filter(|p| is_visible(p)).count();
+    let faint = icon
+        .pixels()
+        .filter(|p| is_visible(p) && p[3] < SOLID_ALPHA)
+        .count();
+    share(faint, visible)
+}
+
+/// Share of the visible pixels that stand out from `background` once composited onto it
+fn contrasting_share(icon: &RgbaImage, background: [u8; 3]) -> f32 {
+    let background_luminance = luminance(background.map(|c| c as f32 / 255.0));
+    let visible: Vec<&Rgba<u8>> = icon.pixels().filter(|p| is_visible(p)).collect();
+    let contrasting = visible
+        .iter()
+        .filter(|pixel| {
+            let alpha = pixel[3] as f32 / 255.0;
+            let composited: [f32; 3] = std::array::from_fn(|c| {
+                (pixel[c] as f32 * alpha + background[c] as f32 * (1.0 - alpha)) / 255.0
+            });
+            contrast(luminance(composited), background_luminance) >= MIN_CONTRAST
+        })
+        .count();
+    share(contrasting, visible.len())
+}
+
+/// Share of the outermost pixels that are visible
+fn edge_share(icon: &RgbaImage) -> f32 {
+    let (width, height) = icon.dimensions();
+    let border: Vec<&Rgba<u8>> = icon
+        .enumerate_pixels()
+        .filter(|(x, y, _)| *x == 0 || *y == 0 || *x == width - 1 || *y == height - 1)
+        .map(|(_, _, pixel)| pixel)
+        .collect();
+    let touching = border.iter().filter(|p| is_visible(p)).count();
+    share(touching, border.len())
+}
+
+/// How much of the icon's width or height (whichever is more) the artwork spans, if any
+fn fill(icon: &RgbaImage) -> Option<f32> {
+    let visible: Vec<(u32, u32)> = icon
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| is_visible(pixel))
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    let span = |coords: &mut dyn Iterator<Item = u32>| {
+        let coords: Vec<u32> = coords.collect();
+        Some(coords.iter().max()? - coords.iter().min()? + 1)
+    };
+    let width = span(&mut visible.iter().map(|(x, _)| *x))?;
+    let height = span(&mut visible.iter().map(|(_, y)| *y))?;
+    Some(width.max(height) as f32 / icon.width() as f32)
+}
+
+fn share(part: usize, whole: usize) -> f32 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f32 / whole as f32
+    }
+}
+
+/// WCAG relative luminance of an sRGB color with channels in 0..1
+fn luminance([r, g, b]: [f32; 3]) -> f32 {
+    let linear = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// WCAG contrast ratio between two luminances
+fn contrast(a: f32, b: f32) -> f32 {
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A solid square of `color` with a small transparent margin
+    fn square(color: [u8; 3]) -> Pixels {
+        let [r, g, b] = color;
+        let image = RgbaImage::from_fn(64, 64, |x, y| {
+            if (4..60).contains(&x) && (4..60).contains(&y) {
+                Rgba([r, g, b, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        Pixels::from_image(&image)
+    }
+
+    #[test]
+    fn warns_about_a_pale_icon_on_light_tabs() {
+        let warnings = check(&square([0xf0, 0xf0, 0xf0]), &Resampling::default());
+        assert_eq!(
+            warnings,
+            ["The icon has little contrast against light browser tabs at 16x16 and 32x32 and may be hard to see"]
+        );
+    }
+
+    #[test]
+    fn warns_about_a_dark_icon_on_dark_tabs() {
+        let warnings = check(&square([0x20, 0x20, 0x20]), &Resampling::default());
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(warnings[0].contains("dark browser tabs"), "{warnings:?}");
+    }
+
+    #[test]
+    fn accepts_an_icon_that_stands_out_on_both() {
+        // Black on the left half, white on the right
+        let image = RgbaImage::from_fn(64, 64, |x, y| match (x, y) {
+            (4..32, 4..60) => Rgba([0, 0, 0, 255]),
+            (32..60, 4..60) => Rgba([255, 255, 255, 255]),
+            _ => Rgba([0, 0, 0, 0]),
+        });
+        let warnings = check(&Pixels::from_image(&image), &Resampling::default());
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+}
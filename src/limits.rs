@@ -0,0 +1,123 @@
+//! Caps on how large a source may be decoded or rendered, so a hostile or broken file
+//! can't exhaust memory.
+
+use crate::error::{FavisError, Result};
+use serde::Serialize;
+
+/// Longest side, in pixels, a source may be decoded or rendered at by default
+pub const DEFAULT_MAX_DIMENSION: u32 = 16384;
+/// Memory, in MiB, processing a source may take by default (about 44 megapixels)
+pub const DEFAULT_MAX_MEMORY: u64 = 1024;
+
+/// Bytes per source pixel at the pipeline's peak: the decoded or rendered RGBA8 image (4),
+/// a copy made while squaring it (4) and the linear-light float buffer every icon is
+/// resampled from (16)
+const WORKING_BYTES_PER_PIXEL: u64 = 24;
+
+/// Decode and render limits for the source image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Limits {
+    /// Longest side in pixels
+    pub max_dimension: u32,
+    /// Memory for processing the source, in MiB: the decoded image and its working buffers
+    pub max_memory: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_dimension: DEFAULT_MAX_DIMENSION,
+            max_memory: DEFAULT_MAX_MEMORY,
+        }
+    }
+}
+
+impl Limits {
+    /// Checks values that come from flags or a config file
+    pub fn validate(&self) -> Result<()> {
+        if self.max_dimension == 0 || self.max_memory == 0 {
+            return Err(FavisError::invalid_option(
+                "--max-dimension and --max-memory must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that a `width`x`height` image fits, before anything is allocated for it.
+    ///
+    /// `what` names the image in the error, e.g. "The source image".
+    pub fn check(&self, what: &str, width: u32, height: u32) -> Result<()> {
+        let longest = width.max(height);
+        if longest > self.max_dimension {
+            return Err(FavisError::limit_exceeded(format!(
+                "{what} is {width}x{height} pixels, more than the {} pixel limit (--max-dimension)",
+                self.max_dimension
+            )));
+        }
+        let bytes = (width as u64 * height as u64).saturating_mul(WORKING_BYTES_PER_PIXEL);
+        if bytes > self.max_bytes() {
+            return Err(FavisError::limit_exceeded(format!(
+                "{what} is {width}x{height} pixels and needs about {} MiB to process, more than the {} MiB limit (--max-memory)",
+                bytes.div_ceil(1024 * 1024),
+                self.max_memory
+            )));
+        }
+        Ok(())
+    }
+
+    /// The same limits for the `image` crate's decoders, whose own buffers get the whole budget
+    pub fn decoder_limits(&self) -> image::Limits {
+        let mut limits = image::Limits::default();
+        limits.max_image_width = Some(self.max_dimension);
+        limits.max_image_height = Some(self.max_dimension);
+        limits.max_alloc = Some(self.max_bytes());
+        limits
+    }
+
+    fn max_bytes(&self) -> u64 {
+        self.max_memory.saturating_mul(1024 * 1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    const LIMITS: Limits = Limits {
+        max_dimension: 1000,
+        max_memory: 1,
+    };
+
+    #[test]
+    fn accepts_images_within_the_limits() {
+        // 200x200 needs 200 * 200 * 24 bytes, just under 1 MiB
+        assert!(LIMITS.check("The source", 200, 200).is_ok());
+        assert!(LIMITS.check("The source", 1000, 1).is_ok());
+    }
+
+    #[test]
+    fn refuses_a_side_over_the_dimension_limit() {
+        let err = LIMITS.check("The source", 1001, 1).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::LimitExceeded);
+        assert!(err.context.contains("--max-dimension"), "{}", err.context);
+    }
+
+    #[test]
+    fn counts_the_working_buffers_against_the_memory_limit() {
+        // 4 bytes per pixel would fit in 1 MiB; the working buffers don't
+        let err = LIMITS.check("The source", 300, 300).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::LimitExceeded);
+        assert!(err.context.contains("--max-memory"), "{}", err.context);
+        assert!(err.context.contains("about 3 MiB"), "{}", err.context);
+    }
+
+    #[test]
+    fn does_not_overflow_on_huge_sizes() {
+        let limits = Limits {
+            max_dimension: u32::MAX,
+            max_memory: u64::MAX,
+        };
+        assert!(limits.check("The source", u32::MAX, u32::MAX).is_ok());
+    }
+}
@@ -1,66 +1,174 @@
 // src/link.rs
 
-//! Generate HTML <link> tags or JSON metadata from a webmanifest
+//! Generate HTML <head> tags or framework snippets from a webmanifest or icon directory
 
+use crate::cli::{HeadGroup, LinkFormat, StatusBarStyle};
 use crate::error::{FavisError, Result};
 use crate::icon_sizes::{get_all_sizes, IconPurpose};
+use crate::limits::Limits;
+use crate::link_format;
+use crate::manifest::MANIFEST_FILE;
+use crate::plan::{Plan, PlanAction};
+use crate::scan;
+use crate::stdio;
 use indicatif::ProgressBar;
 use owo_colors::OwoColorize;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     fs::{self, File},
     io::Write,
+    path::Path,
 };
 
-/// Minimal representation of the 'icons' array in webmanifest
-#[derive(Debug, Deserialize)]
-struct Manifest {
-    icons: Vec<IconEntry>,
+/// Minimal representation of the webmanifest fields used for <head> output
+#[derive(Debug, Default, Deserialize)]
+pub struct Manifest {
+    name: Option<String>,
+    short_name: Option<String>,
+    display: Option<String>,
+    pub theme_color: Option<String>,
+    #[serde(default)]
+    pub icons: Vec<IconEntry>,
 }
 
 #[derive(Debug, Deserialize)]
-struct IconEntry {
-    src: String,
-    sizes: Option<String>,
+pub struct IconEntry {
+    pub src: String,
+    pub sizes: Option<String>,
     #[serde(rename = "type")]
-    mime_type: Option<String>,
+    pub mime_type: Option<String>,
     #[allow(dead_code)]
-    purpose: Option<String>,
+    pub purpose: Option<String>,
+}
+
+/// Where icon information is read from
+#[derive(Debug, Clone, Copy)]
+pub enum LinkSource<'a> {
+    /// A webmanifest file and its `icons` array
+    Manifest(&'a str),
+    /// A directory scanned for favicon files
+    Directory(&'a str),
+}
+
+/// Options controlling which tags are generated and how they are rendered
+#[derive(Debug, Clone)]
+pub struct LinkOptions<'a> {
+    /// URL prefix for every href
+    pub base_url: Option<&'a str>,
+    /// Output format
+    pub format: LinkFormat,
+    /// Tag groups to include, in any order
+    pub groups: Vec<HeadGroup>,
+    /// Value for `apple-mobile-web-app-status-bar-style`
+    pub status_bar_style: StatusBarStyle,
 }
 
 /// Represents a <link> tag for favicon
-#[derive(Debug)]
-struct LinkTag {
-    rel: &'static str,
-    href: String,
-    sizes: Option<String>,
-    type_attr: Option<String>,
+#[derive(Debug, Serialize)]
+pub struct LinkTag {
+    pub rel: &'static str,
+    pub href: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sizes: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_attr: Option<String>,
 }
 
 impl LinkTag {
-    /// Formats as HTML <link ... />
-    fn to_html(&self) -> String {
-        let mut parts = vec![format!("rel=\"{}\"", self.rel)];
-        parts.push(format!("href=\"{}\"", self.href));
+    /// Attribute name/value pairs in output order
+    pub fn attributes(&self) -> Vec<(&'static str, &str)> {
+        let mut attrs = vec![("rel", self.rel), ("href", self.href.as_str())];
         if let Some(s) = &self.sizes {
-            parts.push(format!("sizes=\"{s}\""));
+            attrs.push(("sizes", s));
         }
         if let Some(t) = &self.type_attr {
-            parts.push(format!("type=\"{t}\""));
+            attrs.push(("type", t));
+        }
+        attrs
+    }
+}
+
+/// Represents a <meta name="..." content="..."> tag
+#[derive(Debug, Serialize)]
+pub struct MetaTag {
+    pub name: &'static str,
+    pub content: String,
+}
+
+impl MetaTag {
+    fn new(name: &'static str, content: impl Into<String>) -> Self {
+        Self {
+            name,
+            content: content.into(),
+        }
+    }
+}
+
+/// Any tag that can appear in the generated <head> block
+#[derive(Debug, Serialize)]
+#[serde(tag = "tag", rename_all = "lowercase")]
+pub enum HeadTag {
+    Link(LinkTag),
+    Meta(MetaTag),
+}
+
+impl HeadTag {
+    /// HTML element name
+    pub fn element(&self) -> &'static str {
+        match self {
+            HeadTag::Link(_) => "link",
+            HeadTag::Meta(_) => "meta",
+        }
+    }
+
+    /// Attribute name/value pairs in output order
+    pub fn attributes(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            HeadTag::Link(link) => link.attributes(),
+            HeadTag::Meta(meta) => vec![("name", meta.name), ("content", meta.content.as_str())],
         }
-        format!("<link {}/>", parts.join(" "))
+    }
+
+    /// Formats as HTML <link ... /> or <meta ... />
+    pub fn to_html(&self) -> String {
+        let parts: Vec<String> = self
+            .attributes()
+            .into_iter()
+            .map(|(name, value)| format!("{name}=\"{}\"", escape_attr(value)))
+            .collect();
+        format!("<{} {}/>", self.element(), parts.join(" "))
+    }
+}
+
+/// Escapes a value for use inside a double-quoted HTML attribute
+pub fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+/// Prefix a manifest-relative path with the optional base URL
+fn with_base(base_url: Option<&str>, path: &str) -> String {
+    if let Some(base) = base_url {
+        format!(
+            "{}/{}",
+            base.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    } else {
+        path.to_string()
     }
 }
 
-/// Reads a manifest, builds link tags, and returns HTML snippet
-pub fn generate_links_from_manifest(manifest_path: &str, base_url: Option<&str>) -> Result<String> {
-    // Read and parse manifest file
-    let raw = fs::read_to_string(manifest_path).map_err(|_| {
-        FavisError::file_not_found(format!("Cannot read manifest file: {manifest_path}"))
-    })?;
-    let manifest: Manifest = serde_json::from_str(&raw)
-        .map_err(|_| FavisError::invalid_format("Invalid JSON in manifest.webmanifest"))?;
+/// Returns true if an icon URL points at an ICO file, ignoring any query string
+fn is_ico(src: &str) -> bool {
+    let path = src.split(['?', '#']).next().unwrap_or(src);
+    path.to_lowercase().ends_with(".ico")
+}
 
+/// Builds the sorted, deduplicated icon link tags for a list of manifest icons
+fn build_icon_tags(icons: Vec<IconEntry>, base_url: Option<&str>) -> Vec<LinkTag> {
     // Load all known icon sizes and build a lookup by size string
     let known_sizes = get_all_sizes();
     let mut size_map = std::collections::HashMap::new();
@@ -73,34 +181,25 @@ pub fn generate_links_from_manifest(manifest_path: &str, base_url: Option<&str>)
     let mut seen = std::collections::HashSet::new();
     let mut tags = Vec::new();
 
-    for icon in manifest.icons {
+    for icon in icons {
         // Build href with optional base
-        let href = if let Some(base) = base_url {
-            format!(
-                "{}/{}",
-                base.trim_end_matches('/'),
-                icon.src.trim_start_matches('/')
-            )
-        } else {
-            icon.src.clone()
-        };
+        let href = with_base(base_url, &icon.src);
 
         // Determine rel using icon_sizes.rs metadata
         let rel = if let Some(sizes) = &icon.sizes {
             match size_map.get(sizes) {
                 Some(purposes) if purposes.contains(&IconPurpose::AppleTouch) => "apple-touch-icon",
-                Some(purposes) if purposes.contains(&IconPurpose::Favicon) => {
-                    if icon.src.to_lowercase().ends_with(".ico") {
-                        "shortcut icon"
-                    } else {
-                        "icon"
-                    }
+                Some(purposes) if purposes.contains(&IconPurpose::Favicon) && is_ico(&icon.src) => {
+                    "shortcut icon"
                 }
+                Some(purposes) if purposes.contains(&IconPurpose::Favicon) => "icon",
                 Some(purposes) if purposes.contains(&IconPurpose::Android) => "icon",
                 Some(purposes) if purposes.contains(&IconPurpose::Pwa) => "icon",
+                // Multi-size ICO files list several sizes, e.g. "16x16 32x32"
+                None if is_ico(&icon.src) => "shortcut icon",
                 _ => "icon",
             }
-        } else if icon.src.to_lowercase().ends_with(".ico") {
+        } else if is_ico(&icon.src) {
             "shortcut icon"
         } else {
             "icon"
@@ -121,7 +220,6 @@ pub fn generate_links_from_manifest(manifest_path: &str, base_url: Option<&str>)
         }
     }
 
-    // Generate HTML
     // Sort tags by rel priority, then by numeric size
     const REL_PRIORITY: &[&str] = &["shortcut icon", "icon", "apple-touch-icon"];
     tags.sort_by(|a, b| {
@@ -148,49 +246,374 @@ pub fn generate_links_from_manifest(manifest_path: &str, base_url: Option<&str>)
             })
     });
 
-    let mut html = String::new();
-    for tag in &tags {
-        html.push_str(&tag.to_html());
-        html.push('\n');
+    tags
+}
+
+/// Reads and parses a webmanifest file, or stdin when the path is `-`
+pub fn read_manifest(manifest_path: &str) -> Result<Manifest> {
+    let raw = if stdio::is_stdio(manifest_path) {
+        String::from_utf8(stdio::read_stdin("a webmanifest")?).map_err(|err| {
+            FavisError::invalid_format("Manifest on stdin is not valid UTF-8").with_source(err)
+        })?
+    } else {
+        fs::read_to_string(manifest_path)
+            .map_err(|err| FavisError::read_failed(format!("manifest file {manifest_path}"), err))?
+    };
+    serde_json::from_str(&raw).map_err(|err| {
+        FavisError::invalid_format("Invalid JSON in manifest.webmanifest").with_source(err)
+    })
+}
+
+/// Reads icon information from the source and builds the ordered list of <head> tags
+///
+/// Returns the tags plus any warnings about files skipped while scanning a directory.
+pub fn build_head_tags(
+    source: LinkSource,
+    options: &LinkOptions,
+) -> Result<(Vec<HeadTag>, Vec<String>)> {
+    match source {
+        LinkSource::Manifest(manifest_path) => {
+            let manifest = read_manifest(manifest_path)?;
+            // A manifest piped in on stdin is linked under its usual name
+            let file_name = Path::new(manifest_path)
+                .file_name()
+                .filter(|_| !stdio::is_stdio(manifest_path))
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| MANIFEST_FILE.to_string());
+            Ok((
+                assemble_head_tags(manifest, Some(&file_name), options),
+                Vec::new(),
+            ))
+        }
+        LinkSource::Directory(dir) => {
+            let scanned = scan::scan_directory(dir, &Limits::default())?;
+            let mut warnings = scanned.warnings;
+
+            // Metadata still comes from a manifest if the directory has one
+            let mut manifest = match &scanned.manifest {
+                Some(name) => {
+                    let path = Path::new(dir).join(name);
+                    match read_manifest(&path.to_string_lossy()) {
+                        Ok(manifest) => manifest,
+                        Err(e) => {
+                            warnings.push(format!("{name}: {e}"));
+                            Manifest::default()
+                        }
+                    }
+                }
+                None => Manifest::default(),
+            };
+            manifest.icons = scanned.icons;
+
+            Ok((
+                assemble_head_tags(manifest, scanned.manifest.as_deref(), options),
+                warnings,
+            ))
+        }
     }
-    Ok(html)
 }
 
-/// Public API: Generate HTML link tags from manifest and write to file if requested
-pub fn generate_links(
-    manifest_path: &str,
-    base_url: Option<&str>,
-    output_path: Option<&str>,
+/// Builds the complete, ordered list of <head> tags
+///
+/// Order: application name, theme color, manifest link, icons, then Apple web app metadata.
+fn assemble_head_tags(
+    manifest: Manifest,
+    manifest_href: Option<&str>,
+    options: &LinkOptions,
+) -> Vec<HeadTag> {
+    let wants = |group: HeadGroup| options.groups.contains(&group);
+    let mut tags = Vec::new();
+
+    if wants(HeadGroup::Application) {
+        if let Some(name) = &manifest.name {
+            tags.push(HeadTag::Meta(MetaTag::new("application-name", name)));
+        }
+    }
+
+    if wants(HeadGroup::Theme) {
+        if let Some(color) = &manifest.theme_color {
+            tags.push(HeadTag::Meta(MetaTag::new("theme-color", color)));
+        }
+    }
+
+    if let Some(href) = manifest_href.filter(|_| wants(HeadGroup::Manifest)) {
+        tags.push(HeadTag::Link(LinkTag {
+            rel: "manifest",
+            href: with_base(options.base_url, href),
+            sizes: None,
+            type_attr: None,
+        }));
+    }
+
+    if wants(HeadGroup::Icons) {
+        tags.extend(
+            build_icon_tags(manifest.icons, options.base_url)
+                .into_iter()
+                .map(HeadTag::Link),
+        );
+    }
+
+    if wants(HeadGroup::Apple) {
+        if let Some(title) = manifest.short_name.as_ref().or(manifest.name.as_ref()) {
+            tags.push(HeadTag::Meta(MetaTag::new(
+                "apple-mobile-web-app-title",
+                title,
+            )));
+        }
+
+        // Only app-like display modes launch without browser chrome on iOS
+        let capable = matches!(
+            manifest.display.as_deref(),
+            Some("standalone" | "fullscreen" | "minimal-ui")
+        );
+        if capable {
+            tags.push(HeadTag::Meta(MetaTag::new(
+                "apple-mobile-web-app-capable",
+                "yes",
+            )));
+            tags.push(HeadTag::Meta(MetaTag::new(
+                "apple-mobile-web-app-status-bar-style",
+                options.status_bar_style.to_string(),
+            )));
+        }
+    }
+
+    tags
+}
+
+/// Tags rendered in the requested format, plus anything worth warning about
+struct Rendered {
+    content: String,
+    tags: usize,
+    warnings: Vec<String>,
+}
+
+/// Builds the head tags and renders them in the requested format
+fn render_links(
+    source: LinkSource,
+    options: &LinkOptions,
     progress: Option<&ProgressBar>,
-) -> Result<()> {
+) -> Result<Rendered> {
     if let Some(pb) = progress {
-        pb.set_message(format!("{}", "Reading manifest...".cyan().bold()));
+        let message = match source {
+            LinkSource::Manifest(_) => "Reading manifest...",
+            LinkSource::Directory(_) => "Scanning directory for favicons...",
+        };
+        pb.set_message(format!("{}", message.cyan().bold()));
     }
-    let html = generate_links_from_manifest(manifest_path, base_url)?;
+    let (tags, warnings) = build_head_tags(source, options)?;
 
     if let Some(pb) = progress {
-        pb.set_message(format!("{}", "Generating HTML link tags...".cyan().bold()));
+        pb.set_message(format!(
+            "{} {}",
+            "Generating link tags as".cyan().bold(),
+            options.format.to_string().yellow()
+        ));
     }
+    Ok(Rendered {
+        content: link_format::render(&tags, options.format)?,
+        tags: tags.len(),
+        warnings,
+    })
+}
+
+/// Describes what `generate_links` would write, without writing anything
+pub fn plan_links(
+    source: LinkSource,
+    options: &LinkOptions,
+    output_path: Option<&str>,
+    progress: Option<&ProgressBar>,
+) -> Result<Plan> {
+    let rendered = render_links(source, options, progress)?;
+    let mut plan = Plan {
+        warnings: rendered.warnings,
+        ..Default::default()
+    };
+    let note = format!("{} tag(s) as {}", rendered.tags, options.format);
+    match output_path {
+        Some(path) => {
+            let action = match fs::read_to_string(path) {
+                Ok(existing) if existing == rendered.content => PlanAction::Unchanged,
+                _ => PlanAction::for_path(Path::new(path)),
+            };
+            plan.push(path, action, Some(note));
+        }
+        None => plan.notes.push(format!(
+            "No files would be written: {note} would be printed to stdout."
+        )),
+    }
+    Ok(plan)
+}
+
+/// What `generate_links` produced
+#[derive(Debug, Serialize)]
+pub struct LinkReport {
+    /// File the tags were written to; `None` means the caller should print `content`
+    pub output: Option<String>,
+    pub format: String,
+    pub tags: usize,
+    pub bytes: usize,
+    pub warnings: Vec<String>,
+    /// Rendered tags, when they were not written to a file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+/// Public API: Generate head tags from a manifest or directory and write to file if requested
+pub fn generate_links(
+    source: LinkSource,
+    options: &LinkOptions,
+    output_path: Option<&str>,
+    progress: Option<&ProgressBar>,
+) -> Result<LinkReport> {
+    let rendered = render_links(source, options, progress)?;
+    let mut report = LinkReport {
+        output: output_path.map(str::to_string),
+        format: options.format.to_string(),
+        tags: rendered.tags,
+        bytes: rendered.content.len(),
+        warnings: rendered.warnings,
+        content: None,
+    };
 
     if let Some(path) = output_path {
         if let Some(pb) = progress {
             pb.set_message(format!(
                 "{} {}",
-                "Writing HTML to".cyan().bold(),
+                "Writing link tags to".cyan().bold(),
                 path.yellow()
             ));
         }
         let mut file = File::create(path)?;
-        file.write_all(html.as_bytes())?;
-        if let Some(pb) = progress {
-            pb.set_message(format!(
-                "{}",
-                "HTML link tags written successfully.".green().bold()
-            ));
-        }
+        file.write_all(rendered.content.as_bytes())?;
     } else {
-        // Print to stdout if no output file specified
-        println!("{html}");
+        // The caller prints to stdout, so JSON output can wrap it instead
+        report.content = Some(rendered.content);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stage::TempDir;
+
+    fn options(base_url: Option<&str>) -> LinkOptions<'_> {
+        LinkOptions {
+            base_url,
+            format: LinkFormat::Html,
+            groups: vec![
+                HeadGroup::Icons,
+                HeadGroup::Manifest,
+                HeadGroup::Theme,
+                HeadGroup::Apple,
+                HeadGroup::Application,
+            ],
+            status_bar_style: StatusBarStyle::BlackTranslucent,
+        }
+    }
+
+    fn icon(src: &str, sizes: &str) -> IconEntry {
+        IconEntry {
+            src: src.to_string(),
+            sizes: Some(sizes.to_string()),
+            mime_type: None,
+            purpose: None,
+        }
+    }
+
+    fn html(tags: &[HeadTag]) -> Vec<String> {
+        tags.iter().map(HeadTag::to_html).collect()
+    }
+
+    #[test]
+    fn escapes_attribute_values() {
+        assert_eq!(
+            escape_attr(r#"Tom & "Jerry" <3 'x'"#),
+            "Tom &amp; &quot;Jerry&quot; &lt;3 'x'"
+        );
+    }
+
+    #[test]
+    fn orders_the_head_block() {
+        let manifest = Manifest {
+            name: Some("Acme".into()),
+            short_name: Some("A".into()),
+            display: Some("standalone".into()),
+            theme_color: Some("#336699".into()),
+            icons: vec![
+                icon("/apple-touch-icon.png", "180x180"),
+                icon("/favicon-32x32.png", "32x32"),
+                icon("/favicon.ico", "16x16 32x32"),
+                icon("/favicon-16x16.png", "16x16"),
+                icon("/favicon-copy-32x32.png", "32x32"),
+            ],
+        };
+        let tags = assemble_head_tags(manifest, Some("site.webmanifest"), &options(None));
+        assert_eq!(
+            html(&tags),
+            [
+                r#"<meta name="application-name" content="Acme"/>"#,
+                r##"<meta name="theme-color" content="#336699"/>"##,
+                r#"<link rel="manifest" href="site.webmanifest"/>"#,
+                r#"<link rel="shortcut icon" href="/favicon.ico" sizes="16x16 32x32"/>"#,
+                r#"<link rel="icon" href="/favicon-16x16.png" sizes="16x16"/>"#,
+                r#"<link rel="icon" href="/favicon-32x32.png" sizes="32x32"/>"#,
+                r#"<link rel="apple-touch-icon" href="/apple-touch-icon.png" sizes="180x180"/>"#,
+                r#"<meta name="apple-mobile-web-app-title" content="A"/>"#,
+                r#"<meta name="apple-mobile-web-app-capable" content="yes"/>"#,
+                r#"<meta name="apple-mobile-web-app-status-bar-style" content="black-translucent"/>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_groups_that_were_not_asked_for() {
+        let manifest = Manifest {
+            name: Some("Acme".into()),
+            display: Some("browser".into()),
+            theme_color: Some("#336699".into()),
+            icons: vec![icon("/favicon-32x32.png", "32x32")],
+            ..Default::default()
+        };
+        let mut options = options(None);
+        options.groups = vec![HeadGroup::Icons, HeadGroup::Apple];
+        // `browser` isn't an app-like display mode, so there's no capable tag
+        assert_eq!(
+            html(&assemble_head_tags(
+                manifest,
+                Some("site.webmanifest"),
+                &options
+            )),
+            [
+                r#"<link rel="icon" href="/favicon-32x32.png" sizes="32x32"/>"#,
+                r#"<meta name="apple-mobile-web-app-title" content="Acme"/>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn links_the_manifest_by_its_file_name_under_the_base_url() {
+        let dir = TempDir::new("link");
+        let path = dir.path().join("app.webmanifest");
+        fs::write(
+            &path,
+            r#"{ "icons": [{ "src": "icons/a-32x32.png", "sizes": "32x32" }] }"#,
+        )
+        .unwrap();
+        let source = path.to_string_lossy().into_owned();
+        let (tags, warnings) = build_head_tags(
+            LinkSource::Manifest(&source),
+            &options(Some("https://cdn.example.com/")),
+        )
+        .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            html(&tags),
+            [
+                r#"<link rel="manifest" href="https://cdn.example.com/app.webmanifest"/>"#,
+                r#"<link rel="icon" href="https://cdn.example.com/icons/a-32x32.png" sizes="32x32"/>"#,
+            ]
+        );
     }
-    Ok(())
 }
@@ -0,0 +1,352 @@
+//! Renderers that turn head tags into HTML, JSON, or framework-specific snippets.
+
+use crate::cli::LinkFormat;
+use crate::error::Result;
+use crate::link::{HeadTag, LinkTag, MetaTag};
+
+/// Indentation used by all code-style renderers
+const INDENT: &str = "  ";
+
+/// Render the ordered head tags in the requested format
+pub fn render(tags: &[HeadTag], format: LinkFormat) -> Result<String> {
+    let output = match format {
+        LinkFormat::Html => render_html(tags),
+        LinkFormat::Json => render_json(tags)?,
+        LinkFormat::Jsx => render_jsx(tags, false),
+        LinkFormat::Tsx => render_jsx(tags, true),
+        LinkFormat::Next => render_next(tags),
+        LinkFormat::Vue => render_vue(tags),
+        LinkFormat::Astro => render_astro(tags),
+        LinkFormat::Svelte => render_svelte(tags),
+        LinkFormat::Jinja => render_partial(tags, "{#", "#}"),
+        LinkFormat::Handlebars => render_partial(tags, "{{!--", "--}}"),
+    };
+    Ok(output)
+}
+
+/// Quote a value as a JavaScript string literal
+fn js_string(value: &str) -> String {
+    // JSON strings are valid JavaScript string literals
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+/// Format a tag as a JavaScript object literal, e.g. `{ rel: "icon", href: "/a.png" }`
+fn js_object(tag: &HeadTag) -> String {
+    let fields: Vec<String> = tag
+        .attributes()
+        .into_iter()
+        .map(|(name, value)| format!("{name}: {}", js_string(value)))
+        .collect();
+    format!("{{ {} }}", fields.join(", "))
+}
+
+/// Format a tag as a Next.js icon descriptor, optionally keeping its rel
+fn next_icon_object(tag: &LinkTag, with_rel: bool) -> String {
+    let fields: Vec<String> = tag
+        .attributes()
+        .into_iter()
+        .filter(|(name, _)| with_rel || *name != "rel")
+        .map(|(name, value)| {
+            // Next.js calls the href `url`
+            let key = if name == "href" { "url" } else { name };
+            format!("{key}: {}", js_string(value))
+        })
+        .collect();
+    format!("{{ {} }}", fields.join(", "))
+}
+
+/// Plain HTML, one tag per line
+fn render_html(tags: &[HeadTag]) -> String {
+    let mut html = String::new();
+    for tag in tags {
+        html.push_str(&tag.to_html());
+        html.push('\n');
+    }
+    html
+}
+
+/// JSON array of tag objects
+fn render_json(tags: &[HeadTag]) -> Result<String> {
+    let mut json = serde_json::to_string_pretty(tags)?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// React component returning a fragment of <head> elements
+fn render_jsx(tags: &[HeadTag], typescript: bool) -> String {
+    let mut out = String::new();
+    if typescript {
+        out.push_str("import type { ReactElement } from \"react\";\n\n");
+        out.push_str("export default function Favicons(): ReactElement {\n");
+    } else {
+        out.push_str("export default function Favicons() {\n");
+    }
+    out.push_str(&format!("{INDENT}return (\n{INDENT}{INDENT}<>\n"));
+    for tag in tags {
+        let attrs: Vec<String> = tag
+            .attributes()
+            .into_iter()
+            // JSX string attributes don't process escapes, so values go in as expressions
+            .map(|(name, value)| format!("{name}={{{}}}", js_string(value)))
+            .collect();
+        out.push_str(&format!(
+            "{INDENT}{INDENT}{INDENT}<{} {} />\n",
+            tag.element(),
+            attrs.join(" ")
+        ));
+    }
+    out.push_str(&format!("{INDENT}{INDENT}</>\n{INDENT});\n}}\n"));
+    out
+}
+
+/// Next.js App Router `metadata` (and `viewport` for the theme color)
+fn render_next(tags: &[HeadTag]) -> String {
+    // Next.js groups icons by kind; anything unknown goes to `other` with its rel
+    const GROUPS: [(&str, &str); 3] = [
+        ("icon", "icon"),
+        ("shortcut", "shortcut icon"),
+        ("apple", "apple-touch-icon"),
+    ];
+
+    let links: Vec<&LinkTag> = tags
+        .iter()
+        .filter_map(|t| match t {
+            HeadTag::Link(link) => Some(link),
+            HeadTag::Meta(_) => None,
+        })
+        .collect();
+    let meta = |name: &str| {
+        tags.iter().find_map(|t| match t {
+            HeadTag::Meta(MetaTag { name: n, content }) if *n == name => Some(content.as_str()),
+            _ => None,
+        })
+    };
+    let manifest = links.iter().find(|link| link.rel == "manifest");
+    let icons: Vec<&LinkTag> = links
+        .iter()
+        .copied()
+        .filter(|link| link.rel != "manifest")
+        .collect();
+    let theme_color = meta("theme-color");
+
+    let mut out = if theme_color.is_some() {
+        String::from("import type { Metadata, Viewport } from \"next\";\n\n")
+    } else {
+        String::from("import type { Metadata } from \"next\";\n\n")
+    };
+    out.push_str("export const metadata: Metadata = {\n");
+
+    if let Some(name) = meta("application-name") {
+        out.push_str(&format!("{INDENT}applicationName: {},\n", js_string(name)));
+    }
+    if let Some(link) = manifest {
+        out.push_str(&format!("{INDENT}manifest: {},\n", js_string(&link.href)));
+    }
+
+    if !icons.is_empty() {
+        out.push_str(&format!("{INDENT}icons: {{\n"));
+        for (key, rel) in GROUPS {
+            let entries: Vec<&LinkTag> = icons.iter().copied().filter(|t| t.rel == rel).collect();
+            if entries.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("{INDENT}{INDENT}{key}: [\n"));
+            for tag in entries {
+                out.push_str(&format!(
+                    "{INDENT}{INDENT}{INDENT}{},\n",
+                    next_icon_object(tag, false)
+                ));
+            }
+            out.push_str(&format!("{INDENT}{INDENT}],\n"));
+        }
+
+        let other: Vec<&LinkTag> = icons
+            .iter()
+            .copied()
+            .filter(|t| !GROUPS.iter().any(|(_, rel)| t.rel == *rel))
+            .collect();
+        if !other.is_empty() {
+            out.push_str(&format!("{INDENT}{INDENT}other: [\n"));
+            for tag in other {
+                out.push_str(&format!(
+                    "{INDENT}{INDENT}{INDENT}{},\n",
+                    next_icon_object(tag, true)
+                ));
+            }
+            out.push_str(&format!("{INDENT}{INDENT}],\n"));
+        }
+        out.push_str(&format!("{INDENT}}},\n"));
+    }
+
+    let apple_title = meta("apple-mobile-web-app-title");
+    let apple_capable = meta("apple-mobile-web-app-capable").is_some();
+    let status_bar = meta("apple-mobile-web-app-status-bar-style");
+    if apple_title.is_some() || apple_capable {
+        out.push_str(&format!("{INDENT}appleWebApp: {{\n"));
+        if let Some(title) = apple_title {
+            out.push_str(&format!("{INDENT}{INDENT}title: {},\n", js_string(title)));
+        }
+        if apple_capable {
+            out.push_str(&format!("{INDENT}{INDENT}capable: true,\n"));
+        }
+        if let Some(style) = status_bar {
+            out.push_str(&format!(
+                "{INDENT}{INDENT}statusBarStyle: {},\n",
+                js_string(style)
+            ));
+        }
+        out.push_str(&format!("{INDENT}}},\n"));
+    }
+    out.push_str("};\n");
+
+    // Next.js 14+ expects the theme color in the viewport export
+    if let Some(color) = theme_color {
+        out.push_str("\nexport const viewport: Viewport = {\n");
+        out.push_str(&format!("{INDENT}themeColor: {},\n", js_string(color)));
+        out.push_str("};\n");
+    }
+    out
+}
+
+/// Vue/Nuxt `useHead` call (works with Nuxt 3 and @unhead/vue)
+fn render_vue(tags: &[HeadTag]) -> String {
+    let mut out = String::from("useHead({\n");
+    for element in ["meta", "link"] {
+        let entries: Vec<&HeadTag> = tags.iter().filter(|t| t.element() == element).collect();
+        if entries.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("{INDENT}{element}: [\n"));
+        for tag in entries {
+            out.push_str(&format!("{INDENT}{INDENT}{},\n", js_object(tag)));
+        }
+        out.push_str(&format!("{INDENT}],\n"));
+    }
+    out.push_str("});\n");
+    out
+}
+
+/// Astro component with an empty frontmatter fence
+fn render_astro(tags: &[HeadTag]) -> String {
+    let mut out = String::from("---\n// Favicon links generated by favis\n---\n");
+    out.push_str(&render_html(tags));
+    out
+}
+
+/// Svelte snippet wrapped in <svelte:head>
+fn render_svelte(tags: &[HeadTag]) -> String {
+    let mut out = String::from("<svelte:head>\n");
+    for tag in tags {
+        out.push_str(INDENT);
+        out.push_str(&template_html(tag));
+        out.push('\n');
+    }
+    out.push_str("</svelte:head>\n");
+    out
+}
+
+/// Template partial (Jinja or Handlebars) with a leading comment
+fn render_partial(tags: &[HeadTag], open: &str, close: &str) -> String {
+    let mut out = format!("{open} Favicon links generated by favis {close}\n");
+    for tag in tags {
+        out.push_str(&template_html(tag));
+        out.push('\n');
+    }
+    out
+}
+
+/// HTML for a tag inside a template, where `{` can start an expression; the entity reads
+/// the same once rendered
+fn template_html(tag: &HeadTag) -> String {
+    tag.to_html().replace('{', "&#123;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A value that needs escaping in every format
+    const NAME: &str = r#"Joe "Pizza" <b> \ {{ name }}"#;
+
+    fn tags() -> Vec<HeadTag> {
+        vec![
+            HeadTag::Link(LinkTag {
+                rel: "icon",
+                href: "/favicon-32x32.png".to_string(),
+                sizes: Some("32x32".to_string()),
+                type_attr: Some("image/png".to_string()),
+            }),
+            HeadTag::Meta(MetaTag {
+                name: "application-name",
+                content: NAME.to_string(),
+            }),
+        ]
+    }
+
+    fn rendered(format: LinkFormat) -> String {
+        render(&tags(), format).unwrap()
+    }
+
+    #[test]
+    fn html_escapes_attribute_values() {
+        assert_eq!(
+            rendered(LinkFormat::Html),
+            concat!(
+                "<link rel=\"icon\" href=\"/favicon-32x32.png\" sizes=\"32x32\" type=\"image/png\"/>\n",
+                "<meta name=\"application-name\" content=\"Joe &quot;Pizza&quot; &lt;b> \\ {{ name }}\"/>\n",
+            )
+        );
+    }
+
+    #[test]
+    fn json_keeps_values_verbatim() {
+        let json: serde_json::Value = serde_json::from_str(&rendered(LinkFormat::Json)).unwrap();
+        assert_eq!(json[0]["tag"], "link");
+        assert_eq!(json[0]["type"], "image/png");
+        assert_eq!(json[1]["content"], NAME);
+    }
+
+    #[test]
+    fn jsx_and_tsx_use_expression_attributes() {
+        let expected =
+            r#"<meta name={"application-name"} content={"Joe \"Pizza\" <b> \\ {{ name }}"} />"#;
+        for format in [LinkFormat::Jsx, LinkFormat::Tsx] {
+            let out = rendered(format);
+            assert!(out.contains(expected), "{out}");
+            assert!(out.contains(r#"<link rel={"icon"} href={"/favicon-32x32.png"}"#));
+        }
+        assert!(rendered(LinkFormat::Tsx).contains("Favicons(): ReactElement"));
+        assert!(!rendered(LinkFormat::Jsx).contains("ReactElement"));
+    }
+
+    #[test]
+    fn next_puts_icons_in_metadata() {
+        let out = rendered(LinkFormat::Next);
+        assert!(
+            out.contains(r#"applicationName: "Joe \"Pizza\" <b> \\ {{ name }}","#),
+            "{out}"
+        );
+        assert!(out.contains(r#"{ url: "/favicon-32x32.png", sizes: "32x32", type: "image/png" }"#));
+        assert!(!out.contains("Viewport"));
+    }
+
+    #[test]
+    fn templates_never_see_an_expression() {
+        for (format, comment) in [
+            (
+                LinkFormat::Jinja,
+                "{# Favicon links generated by favis #}\n",
+            ),
+            (
+                LinkFormat::Handlebars,
+                "{{!-- Favicon links generated by favis --}}\n",
+            ),
+        ] {
+            let out = rendered(format);
+            let body = out.strip_prefix(comment).expect(&out);
+            assert!(!body.contains('{'), "{out}");
+            assert!(body.contains("&#123;&#123; name }}"), "{out}");
+        }
+        assert!(!rendered(LinkFormat::Svelte).contains("{{"));
+    }
+}
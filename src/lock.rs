@@ -0,0 +1,177 @@
+//! Build lock file recording what favis generated, for incremental re-runs.
+
+use crate::error::{FavisError, Result};
+use crate::fingerprint::sha256_hex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// File name of the lock file inside the output directory
+pub const LOCK_FILE: &str = "favis.lock";
+
+/// One generated output as recorded in the lock file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedOutput {
+    /// File name on disk (fingerprinted in `name` cache-bust mode)
+    pub file: String,
+    /// How the manifest references the file
+    pub reference: String,
+    /// SHA-256 of the file contents as written by favis
+    pub hash: String,
+    /// Hash of everything that went into producing the file
+    pub inputs: String,
+}
+
+/// Contents of `favis.lock`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockFile {
+    /// Version of favis that wrote the lock file
+    pub favis_version: String,
+    /// Source image path as given on the command line
+    pub source: String,
+    /// SHA-256 of the source file
+    pub source_hash: String,
+    /// Snapshot of the options used, for humans reading the file
+    pub options: serde_json::Value,
+    /// Outputs keyed by their plain name, e.g. `favicon-32x32.png`
+    pub outputs: BTreeMap<String, LockedOutput>,
+}
+
+/// State of an expected output compared to the lock file and the disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputState {
+    /// Never generated, or the file is gone
+    Missing,
+    /// Generated with different inputs
+    Stale,
+    /// On disk and identical to what favis wrote with the same inputs
+    UpToDate,
+    /// On disk but its contents differ from what favis wrote
+    Modified,
+}
+
+impl LockFile {
+    /// Reads the lock file from `out_dir`; only a missing file means "no lock".
+    ///
+    /// A lock that exists but can't be read is an error rather than ignored, since without it
+    /// hand-edited outputs would be overwritten unnoticed.
+    pub fn read(out_dir: &str) -> Result<Option<Self>> {
+        let path = Path::new(out_dir).join(LOCK_FILE);
+        let unreadable = || FavisError::unreadable_lock(path.display().to_string());
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(unreadable().with_source(err)),
+        };
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|err| unreadable().with_source(err))
+    }
+
+    /// Writes the lock file into `out_dir`
+    pub fn write(&self, out_dir: &str) -> Result<()> {
+        let path = Path::new(out_dir).join(LOCK_FILE);
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&path, json).map_err(|err| {
+            FavisError::write_error(format!("Cannot write lock file: {}", path.display()))
+                .with_source(err)
+        })
+    }
+
+    /// Compares an expected output with the lock entry and the file on disk
+    pub fn state_of(&self, out_dir: &str, name: &str, inputs: &str) -> OutputState {
+        let Some(locked) = self.outputs.get(name) else {
+            return OutputState::Missing;
+        };
+        let Ok(bytes) = fs::read(Path::new(out_dir).join(&locked.file)) else {
+            return OutputState::Missing;
+        };
+        if sha256_hex(&bytes) != locked.hash {
+            OutputState::Modified
+        } else if locked.inputs != inputs {
+            OutputState::Stale
+        } else {
+            OutputState::UpToDate
+        }
+    }
+}
+
+/// Hashes the parts that determine an output into a single input key
+pub fn inputs_hash(parts: &[&str]) -> String {
+    sha256_hex(parts.join("\0").as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stage::TempDir;
+
+    fn lock_with(file: &str, contents: &[u8], inputs: &str) -> LockFile {
+        let output = LockedOutput {
+            file: file.to_string(),
+            reference: file.to_string(),
+            hash: sha256_hex(contents),
+            inputs: inputs.to_string(),
+        };
+        LockFile {
+            favis_version: "0.0.0".to_string(),
+            source: "logo.svg".to_string(),
+            source_hash: String::new(),
+            options: serde_json::Value::Null,
+            outputs: BTreeMap::from([("favicon-32x32.png".to_string(), output)]),
+        }
+    }
+
+    #[test]
+    fn tells_the_four_output_states_apart() {
+        let temp = TempDir::new("lock");
+        let (dir, out_dir) = (temp.path(), temp.path_str());
+        let name = "favicon-32x32.png";
+        let lock = lock_with("favicon-32x32.abcd1234.png", b"icon", "inputs");
+
+        // Not in the lock, and in the lock but gone from disk
+        assert_eq!(
+            lock.state_of(&out_dir, "favicon.ico", "inputs"),
+            OutputState::Missing
+        );
+        assert_eq!(
+            lock.state_of(&out_dir, name, "inputs"),
+            OutputState::Missing
+        );
+
+        // The lock's file name is what's looked up, not the plain name
+        fs::write(dir.join("favicon-32x32.abcd1234.png"), b"icon").unwrap();
+        assert_eq!(
+            lock.state_of(&out_dir, name, "inputs"),
+            OutputState::UpToDate
+        );
+        assert_eq!(lock.state_of(&out_dir, name, "changed"), OutputState::Stale);
+
+        // Hand edits win over changed inputs, so they're never silently overwritten
+        fs::write(dir.join("favicon-32x32.abcd1234.png"), b"edited").unwrap();
+        assert_eq!(
+            lock.state_of(&out_dir, name, "inputs"),
+            OutputState::Modified
+        );
+        assert_eq!(
+            lock.state_of(&out_dir, name, "changed"),
+            OutputState::Modified
+        );
+    }
+
+    #[test]
+    fn only_a_missing_lock_reads_as_none() {
+        let temp = TempDir::new("lock");
+        let (dir, out_dir) = (temp.path(), temp.path_str());
+
+        assert!(LockFile::read(&out_dir).unwrap().is_none());
+        lock_with("favicon-32x32.png", b"icon", "inputs")
+            .write(&out_dir)
+            .unwrap();
+        assert!(LockFile::read(&out_dir).unwrap().is_some());
+        fs::write(dir.join(LOCK_FILE), "{ not json").unwrap();
+        assert!(LockFile::read(&out_dir).is_err());
+    }
+}
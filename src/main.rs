@@ -1,26 +1,56 @@
 //! Main entrypoint for the `favis` CLI.
 
-use clap::{CommandFactory, Parser};
+use clap::{CommandFactory, Parser, ValueEnum};
 use owo_colors::OwoColorize;
 
+use std::path::PathBuf;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
 
+mod archive;
+mod batch;
+mod browserconfig;
 mod cli;
+mod color;
+mod config;
+mod diff;
 mod error;
+mod fingerprint;
+mod fit;
+mod generate;
+mod icc;
 mod img;
+mod legibility;
+mod limits;
 mod link;
+mod link_format;
+mod lock;
 mod manifest;
+mod output;
+mod plan;
+mod preview;
 mod progress;
+mod resample;
+mod scan;
+mod stage;
+mod stdio;
 mod svg;
+mod term_preview;
+mod watch;
 
 use error::{FavisError, Result};
 
-use crate::progress::create_spinner;
-use crate::svg::PixmapExt;
-use cli::{Cli, Commands, SizeLevel};
+use crate::config::Config;
+use crate::generate::{FileStatus, GenerateOptions};
+use crate::limits::Limits;
+use crate::manifest::ManifestMetadata;
+use crate::output::{format_bytes, Output, Verbosity};
+use crate::plan::Plan;
+use crate::resample::Resampling;
+use crate::svg::SvgOptions;
+use cli::{Cli, Commands, GenerateArgs, HeadGroup};
 mod icon_sizes;
 
 fn main() -> Result<()> {
@@ -37,185 +67,378 @@ fn main() -> Result<()> {
     })
     .expect("Error setting Ctrl+C handler");
 
+    let output = Output::new(cli.json, cli.quiet, cli.verbose);
+    let command = match &cli.command {
+        Some(Commands::Generate(_)) => "generate",
+        Some(Commands::Link { .. }) => "link",
+        Some(Commands::Batch { .. }) => "batch",
+        Some(Commands::Preview { .. }) => "preview",
+        Some(Commands::Diff { .. }) => "diff",
+        None => "favis",
+    };
+
     // Run the CLI with cancellation support
-    if let Err(err) = run_cli(cli, cancelled) {
-        err.display_friendly();
-        std::process::exit(1);
+    if let Err(err) = run_cli(cli, output, cancelled) {
+        output.error(command, &err);
+        std::process::exit(err.kind.exit_code());
     }
 
     Ok(())
 }
 
-fn run_cli(cli: Cli, cancelled: Arc<AtomicBool>) -> Result<()> {
+/// Builds generate options from the flags, filling gaps from the config file
+fn build_generate_options(args: &GenerateArgs) -> Result<GenerateOptions> {
+    // Flags take precedence over values from the config file
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let metadata = ManifestMetadata {
+        name: args.name.clone(),
+        short_name: args.short_name.clone(),
+        start_url: args.start_url.clone(),
+        display: args.display,
+        theme_color: args.theme_color.clone(),
+        background_color: args.background_color.clone(),
+    }
+    .or(config.manifest);
+
+    Ok(GenerateOptions {
+        source: args.source.clone(),
+        output: args.output.clone(),
+        priority: args.coverage.priority(),
+        manifest: args.manifest,
+        raster_ok: args.raster_ok,
+        metadata,
+        cache_bust: args.cache_bust,
+        fit: args.fit,
+        trim: args.trim,
+        resampling: Resampling {
+            filter: args.filter,
+            small_filter: args.small_filter,
+            sharpen: args.sharpen,
+        },
+        srgb_chunk: args.srgb_chunk,
+        preview: args.preview,
+        svg: SvgOptions {
+            font_dirs: args.font_dirs.iter().map(PathBuf::from).collect(),
+            stylesheet: args
+                .stylesheet
+                .as_deref()
+                .map(svg::read_stylesheet)
+                .transpose()?,
+            current_color: args.current_color.clone(),
+            dpi: args.dpi,
+            id: args.svg_id.clone(),
+            viewbox: args.viewbox,
+            ..Default::default()
+        },
+        limits: Limits {
+            max_dimension: args.max_dimension,
+            max_memory: args.max_memory,
+        },
+        force: args.force,
+    })
+}
+
+fn run_cli(cli: Cli, output: Output, cancelled: Arc<AtomicBool>) -> Result<()> {
     match cli.command {
-        Some(Commands::Generate {
-            source,
-            coverage,
-            manifest: gen_manifest,
-            output,
-            raster_ok,
-        }) => {
-            // Validate source file exists
-            if !std::path::Path::new(&source).exists() {
-                return Err(FavisError::file_not_found(&source));
-            }
-
-            // Check file extension to determine format
-            let source_lower = source.to_lowercase();
-            let is_svg = source_lower.ends_with(".svg");
-            let is_png = source_lower.ends_with(".png");
-
-            // Validate that the file is a supported image format
-            // Primary focus: SVG (vector graphics)
-            // Secondary support: PNG (raster, with quality warnings)
-            if !is_svg && !is_png {
-                return Err(FavisError::invalid_format(
-                    "Oops! That file format isn't supported.",
+        Some(Commands::Generate(args)) => {
+            // Setup progress spinner
+            let spinner = output.spinner("Starting favicon generation");
+
+            if stdio::is_stdio(&args.source) && args.watch {
+                return Err(FavisError::invalid_option(
+                    "--watch needs a source file; stdin can only be read once",
                 ));
             }
-
-            // Check if using PNG (raster) and require explicit approval
-            if is_png && !raster_ok {
-                return Err(FavisError::invalid_format(
-                    "PNG detected! You'll need the --raster-ok flag to continue.",
+            // `--output -` streams the set to stdout as an archive. clap already refuses
+            // `--archive` with --watch and --dry-run; the stdout form is checked here
+            let archive_path = args
+                .archive
+                .as_deref()
+                .or(stdio::is_stdio(&args.output).then_some(stdio::STDIO_PATH));
+            if stdio::is_stdio(&args.output) && (args.watch || args.dry_run) {
+                return Err(FavisError::invalid_option(
+                    "--output - streams an archive and can't be combined with --watch or --dry-run",
                 ));
             }
-
-            // Setup progress spinner
-            let spinner = create_spinner("Starting favicon generation");
-
-            // Show warning for PNG images if proceeding
-            if is_png && raster_ok {
-                spinner.set_message(format!(
-                    "{} PNG raster image quality may be poor at larger sizes",
-                    "Warning:".yellow().bold()
+            if archive_path.is_some_and(stdio::is_stdio) && output.json {
+                return Err(FavisError::invalid_option(
+                    "Streaming an archive uses stdout, so it can't be combined with --json",
                 ));
-                std::thread::sleep(std::time::Duration::from_millis(1500)); // Show warning briefly
             }
 
-            // Convert CLI SizeLevel to internal IconPriority
-            let priority = match coverage {
-                SizeLevel::Required => icon_sizes::IconPriority::Required,
-                SizeLevel::Recommended => icon_sizes::IconPriority::Recommended,
-                SizeLevel::Extended => icon_sizes::IconPriority::Extended,
-            };
+            if args.watch {
+                let mut paths = vec![PathBuf::from(&args.source)];
+                paths.extend(args.config.as_ref().map(PathBuf::from));
+                paths.extend(args.stylesheet.as_ref().map(PathBuf::from));
+                return watch::watch(
+                    &paths,
+                    || build_generate_options(&args),
+                    &spinner,
+                    output,
+                    cancelled,
+                );
+            }
 
-            // Get the appropriate sizes based on priority
-            let png_sizes = icon_sizes::get_png_sizes(priority);
-            let ico_sizes = icon_sizes::get_ico_sizes(priority);
-
-            spinner.set_message(format!(
-                "{} {}",
-                "Processing source file:".cyan().bold(),
-                source.yellow()
-            ));
-
-            // SVG support: detect extension
-            if is_svg {
-                spinner.set_message(format!("{}", "Loading SVG file...".cyan().bold()));
-                let data = std::fs::read(&source).map_err(|_| {
-                    FavisError::file_not_found(format!("Cannot read SVG file: {source}"))
+            let options = build_generate_options(&args)?;
+            if args.dry_run {
+                let plan = generate::plan(&options, &spinner).inspect_err(|_| {
+                    spinner.abandon();
                 })?;
+                spinner.finish_and_clear();
+                print_plan(&output, "generate", &plan);
+                return Ok(());
+            }
 
-                // Validate SVG data
-                if data.is_empty() {
-                    return Err(FavisError::invalid_svg("SVG file is empty"));
-                }
-
-                // First render the SVG at its original size
-                spinner.set_message(format!("{}", "Rendering SVG to bitmap...".cyan().bold()));
-                let pixmap = svg::render_svg_original_size(&data, Some(&spinner))?;
-
-                // Convert the Pixmap to a DynamicImage
-                spinner.set_message(format!("{}", "Converting to image format...".cyan().bold()));
-                let original_image = pixmap.to_dynamic_image()?;
-
-                // Create output directory if it doesn't exist
-                if std::fs::create_dir_all(&output).is_err() {
-                    return Err(FavisError::write_error(format!(
-                        "Cannot create output directory: {output}"
-                    )));
-                }
-
-                // Create a temporary file path for the original-sized PNG
-                let temp_dir = std::env::temp_dir();
-                let temp_file = temp_dir.join("favis_temp_original.png");
-                let temp_path = temp_file.to_string_lossy();
-
-                // Save the original image to the temp file
-                spinner.set_message(format!("{}", "Saving temporary PNG file...".cyan().bold()));
-                original_image
-                    .save(&temp_file)
-                    .map_err(|_| FavisError::write_error("Cannot save temporary PNG file"))?;
-
-                // Now process it like a regular PNG
-                match img::process(
-                    &temp_path,
-                    &output,
-                    &png_sizes,
-                    &ico_sizes,
-                    Some(&spinner),
-                    cancelled.clone(),
-                ) {
-                    Ok(_) => {}
-                    Err(ref e) if e.to_string().contains("cancelled") => {
-                        spinner.abandon();
-                        return Err(FavisError::user_cancelled());
-                    }
-                    Err(e) => return Err(e),
-                }
-
-                // Clean up the temporary file
-                spinner.set_message(format!(
-                    "{}",
-                    "Cleaning up temporary files...".cyan().bold()
-                ));
-                if temp_file.exists() {
-                    let _ = std::fs::remove_file(temp_file); // Ignore cleanup errors
-                }
-            } else {
-                match img::process(
-                    &source,
-                    &output,
-                    &png_sizes,
-                    &ico_sizes,
-                    Some(&spinner),
-                    cancelled.clone(),
-                ) {
-                    Ok(_) => {}
-                    Err(ref e) if e.to_string().contains("cancelled") => {
-                        spinner.abandon();
-                        return Err(FavisError::user_cancelled());
-                    }
-                    Err(e) => return Err(e),
-                }
+            let report = match archive_path {
+                Some(path) => archive::run(&options, path, &spinner, cancelled),
+                None => generate::run(&options, &spinner, cancelled),
             }
+            .inspect_err(|_| {
+                spinner.abandon();
+            })?;
 
-            if gen_manifest {
-                manifest::generate_manifest(&output, priority, Some(&spinner))?;
+            if output.json {
+                output.result("generate", &report);
+                return Ok(());
+            }
+            for warning in &report.warnings {
+                output.warn(&spinner, warning);
+            }
+            for file in &report.files {
+                let sizes: Vec<String> = file.sizes.iter().map(|s| format!("{s}x{s}")).collect();
+                let status = match file.status {
+                    FileStatus::Written => "written  ",
+                    FileStatus::Unchanged => "unchanged",
+                };
+                output.detail(
+                    &spinner,
+                    format!(
+                        "  {status}  {}  {}",
+                        file.path,
+                        format!(
+                            "({})",
+                            [sizes.join(" "), format_bytes(file.bytes)].join(", ")
+                        )
+                        .dimmed()
+                    ),
+                );
             }
 
-            spinner.finish_with_message(format!(
-                "{} {}",
-                "✓".green().bold(),
-                "All favicon assets generated successfully!".green().bold()
-            ));
+            let written = report.count(FileStatus::Written);
+            if let Some(path) = &report.archive {
+                output.finish(
+                    &spinner,
+                    format!(
+                        "{} {} {} {}",
+                        "✓".green().bold(),
+                        "Favicon archive written to".green().bold(),
+                        if stdio::is_stdio(path) {
+                            "stdout"
+                        } else {
+                            path
+                        }
+                        .yellow(),
+                        format!("({} files)", report.files.len()).dimmed()
+                    ),
+                );
+            } else if written == 0 {
+                output.finish(
+                    &spinner,
+                    format!(
+                        "{} {}",
+                        "✓".green().bold(),
+                        "All favicon assets are already up to date!".green().bold()
+                    ),
+                );
+            } else {
+                output.finish(
+                    &spinner,
+                    format!(
+                        "{} {} {}",
+                        "✓".green().bold(),
+                        "All favicon assets generated successfully!".green().bold(),
+                        format!(
+                            "({} written, {} unchanged)",
+                            written,
+                            report.count(FileStatus::Unchanged)
+                        )
+                        .dimmed()
+                    ),
+                );
+            }
         }
         Some(Commands::Link {
             manifest,
+            scan,
             base,
-            output,
+            format,
+            include,
+            exclude,
+            status_bar_style,
+            output: output_path,
+            dry_run,
         }) => {
             // Create spinner for progress indication
-            let spinner = create_spinner("Generating HTML link tags");
+            let spinner = output.spinner("Generating HTML link tags");
+
+            // Start from the requested groups (or all of them) and drop exclusions
+            let groups = if include.is_empty() {
+                HeadGroup::value_variants().to_vec()
+            } else {
+                include
+            };
+            let options = link::LinkOptions {
+                base_url: base.as_deref(),
+                format,
+                groups: groups
+                    .into_iter()
+                    .filter(|group| !exclude.contains(group))
+                    .collect(),
+                status_bar_style,
+            };
+
+            // clap guarantees exactly one of the manifest or --scan is present
+            let source = match (&manifest, &scan) {
+                (_, Some(dir)) => link::LinkSource::Directory(dir),
+                (Some(path), None) => link::LinkSource::Manifest(path),
+                (None, None) => unreachable!("clap requires a manifest or --scan"),
+            };
+
+            if dry_run {
+                let plan =
+                    link::plan_links(source, &options, output_path.as_deref(), Some(&spinner))?;
+                spinner.finish_and_clear();
+                print_plan(&output, "link", &plan);
+                return Ok(());
+            }
 
             // Call the link generation function
-            link::generate_links(
-                &manifest,
-                base.as_deref(),
-                output.as_deref(),
-                Some(&spinner),
+            let report =
+                link::generate_links(source, &options, output_path.as_deref(), Some(&spinner))?;
+
+            if output.json {
+                spinner.finish_and_clear();
+                output.result("link", &report);
+                return Ok(());
+            }
+            for warning in &report.warnings {
+                output.warn(&spinner, warning);
+            }
+            match (&report.output, &report.content) {
+                (Some(path), _) => {
+                    output.detail(
+                        &spinner,
+                        format!(
+                            "  {} tag(s) as {}, {}",
+                            report.tags,
+                            report.format,
+                            format_bytes(report.bytes as u64)
+                        ),
+                    );
+                    output.finish(
+                        &spinner,
+                        format!(
+                            "{} {} {}",
+                            "✓".green().bold(),
+                            "Link tags written to".green().bold(),
+                            path.yellow()
+                        ),
+                    );
+                }
+                // Print to stdout if no output file specified
+                (None, Some(content)) => {
+                    spinner.finish_and_clear();
+                    println!("{content}");
+                }
+                (None, None) => spinner.finish_and_clear(),
+            }
+        }
+        Some(Commands::Batch {
+            config,
+            parallel,
+            force,
+        }) => {
+            let report = batch::run(
+                &config,
+                parallel.map(usize::from),
+                force,
+                &output,
+                cancelled,
             )?;
+
+            if output.json {
+                output.report("batch", report.failed == 0, &report);
+            } else {
+                for job in &report.jobs {
+                    if let Some(err) = &job.error {
+                        anstream::eprintln!();
+                        anstream::eprintln!("{} {}", "Job failed:".red().bold(), job.name.bold());
+                        err.display_friendly();
+                    }
+                    for warning in job.report.iter().flat_map(|r| &r.warnings) {
+                        anstream::eprintln!(
+                            "{} {}: {warning}",
+                            "Warning:".yellow().bold(),
+                            job.name
+                        );
+                    }
+                }
+                let total = report.jobs.len();
+                if report.failed == 0 && output.verbosity > Verbosity::Quiet {
+                    anstream::eprintln!(
+                        "{} {}",
+                        "✓".green().bold(),
+                        format!("All {total} jobs succeeded!").green().bold()
+                    );
+                } else if report.failed > 0 {
+                    anstream::eprintln!(
+                        "{} {}",
+                        "✗".red().bold(),
+                        format!("{} of {total} jobs failed", report.failed)
+                            .red()
+                            .bold()
+                    );
+                }
+            }
+            // Every job already reported its own error
+            if report.failed > 0 {
+                std::process::exit(report.exit_code());
+            }
+        }
+        Some(Commands::Preview {
+            path,
+            protocol,
+            background,
+            scale,
+        }) => {
+            if output.json {
+                return Err(FavisError::invalid_option(
+                    "favis preview draws in the terminal, so it can't be combined with --json",
+                ));
+            }
+            let options = term_preview::PreviewOptions {
+                protocol,
+                background,
+                scale,
+            };
+            term_preview::run(&path, &options)?;
+        }
+        Some(Commands::Diff {
+            before,
+            after,
+            images,
+        }) => {
+            let spinner = output.spinner("Comparing icons");
+            let report = diff::run(&before, &after, images.as_deref())?;
+            spinner.finish_and_clear();
+            if output.json {
+                output.result("diff", &report);
+            } else {
+                report.print();
+            }
         }
         None => {
             // If no subcommand, print help and exit
@@ -225,3 +448,15 @@ fn run_cli(cli: Cli, cancelled: Arc<AtomicBool>) -> Result<()> {
     }
     Ok(())
 }
+
+/// Prints a dry-run plan as text or JSON
+fn print_plan(output: &Output, command: &str, plan: &Plan) {
+    if output.json {
+        output.result(command, plan);
+        return;
+    }
+    for warning in &plan.warnings {
+        anstream::eprintln!("{} {}", "Warning:".yellow().bold(), warning);
+    }
+    plan.print();
+}
@@ -1,15 +1,95 @@
 //! Web manifest generator for PWA icons.
 
+use crate::cli::DisplayMode;
+use crate::color::normalize_color;
 use crate::error::Result;
+use crate::fingerprint::AssetRefs;
+use crate::plan::FieldChange;
 use indicatif::ProgressBar;
 use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{collections::HashMap, fs, path::Path};
 
 use crate::icon_sizes::{filter_by_priority, IconPriority};
 
+/// File name of the generated manifest
+pub const MANIFEST_FILE: &str = "manifest.webmanifest";
+
+/// Special `theme_color` value that picks the color from the source image
+pub const AUTO_COLOR: &str = "auto";
+
+/// Manifest metadata set from the command line or a config file.
+///
+/// Fields left as `None` keep whatever the existing manifest already has.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display: Option<DisplayMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_color: Option<String>,
+}
+
+impl ManifestMetadata {
+    /// Fills fields that are unset here with values from `fallback`
+    pub fn or(self, fallback: ManifestMetadata) -> Self {
+        Self {
+            name: self.name.or(fallback.name),
+            short_name: self.short_name.or(fallback.short_name),
+            start_url: self.start_url.or(fallback.start_url),
+            display: self.display.or(fallback.display),
+            theme_color: self.theme_color.or(fallback.theme_color),
+            background_color: self.background_color.or(fallback.background_color),
+        }
+    }
+
+    /// Returns true if no field is set
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.short_name.is_none()
+            && self.start_url.is_none()
+            && self.display.is_none()
+            && self.theme_color.is_none()
+            && self.background_color.is_none()
+    }
+
+    /// Returns true if the theme color should be extracted from the source image
+    pub fn wants_auto_theme_color(&self) -> bool {
+        self.theme_color
+            .as_deref()
+            .is_some_and(|c| c.eq_ignore_ascii_case(AUTO_COLOR))
+    }
+
+    /// Validates color strings (`theme_color` may also be `auto`)
+    pub fn validate(&self) -> Result<()> {
+        self.clone().normalize_colors().map(|_| ())
+    }
+
+    /// Validates the colors and rewrites them as lowercase hex, keeping an `auto` theme color
+    pub fn normalize_colors(mut self) -> Result<Self> {
+        if let Some(color) = &self.theme_color {
+            if !self.wants_auto_theme_color() {
+                self.theme_color = Some(normalize_color("theme color", color)?);
+            }
+        }
+        if let Some(color) = &self.background_color {
+            self.background_color = Some(normalize_color("background color", color)?);
+        }
+        Ok(self)
+    }
+}
+
 /// Icon entry in the webmanifest
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 struct ManifestIcon {
     src: String,
     sizes: String,
@@ -18,7 +98,7 @@ struct ManifestIcon {
 }
 
 /// Simplified webmanifest structure
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 struct Manifest {
     #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
@@ -49,12 +129,18 @@ fn read_existing_manifest(path: &Path) -> Result<Option<Manifest>> {
     }
 }
 
-/// Generates or updates a `manifest.webmanifest` in `out_dir` using provided priority level.
-pub fn generate_manifest(
+/// Builds the manifest a run would write, alongside the one currently on disk.
+///
+/// Metadata fields that are set overwrite the existing manifest; `theme_color` must already
+/// be resolved (not `auto`). Icon sources are looked up in `asset_refs` so the manifest
+/// references fingerprinted names when cache busting is enabled.
+fn build_manifest(
     out_dir: &str,
     priority: IconPriority,
+    metadata: &ManifestMetadata,
+    asset_refs: &AssetRefs,
     progress: Option<&ProgressBar>,
-) -> Result<()> {
+) -> Result<(Option<Manifest>, Manifest)> {
     if let Some(pb) = progress {
         pb.set_message(format!("{}", "Creating web manifest...".cyan().bold()));
     }
@@ -65,17 +151,22 @@ pub fn generate_manifest(
     // Create manifest icons with standardized format (no purpose field)
     let icons: Vec<ManifestIcon> = icon_sizes
         .iter()
-        .map(|size| ManifestIcon {
-            src: format!("favicon-{}x{}.png", size.size, size.size),
-            sizes: format!("{}x{}", size.size, size.size),
-            mime_type: "image/png".into(),
+        .map(|size| {
+            let file_name = format!("favicon-{}x{}.png", size.size, size.size);
+            ManifestIcon {
+                // Point at the fingerprinted name when cache busting is on
+                src: asset_refs.get(&file_name).cloned().unwrap_or(file_name),
+                sizes: format!("{}x{}", size.size, size.size),
+                mime_type: "image/png".into(),
+            }
         })
         .collect();
 
-    let path = Path::new(out_dir).join("manifest.webmanifest");
+    let path = Path::new(out_dir).join(MANIFEST_FILE);
 
     // Try to read existing manifest
-    let mut manifest = match read_existing_manifest(&path)? {
+    let existing = read_existing_manifest(&path)?;
+    let mut manifest = match existing.clone() {
         Some(existing) => {
             if let Some(pb) = progress {
                 pb.set_message(format!("{}", "Updating existing manifest...".cyan().bold()));
@@ -102,8 +193,42 @@ pub fn generate_manifest(
         }
     };
 
-    // Update only the icons field
+    // Update icons, plus any metadata that was explicitly provided
     manifest.icons = icons;
+    let metadata = metadata.clone();
+    if let Some(name) = metadata.name {
+        manifest.name = Some(name);
+    }
+    if let Some(short_name) = metadata.short_name {
+        manifest.short_name = Some(short_name);
+    }
+    if let Some(start_url) = metadata.start_url {
+        manifest.start_url = Some(start_url);
+    }
+    if let Some(display) = metadata.display {
+        manifest.display = Some(display.to_string());
+    }
+    if let Some(theme_color) = metadata.theme_color {
+        manifest.theme_color = Some(theme_color);
+    }
+    if let Some(background_color) = metadata.background_color {
+        manifest.background_color = Some(background_color);
+    }
+
+    Ok((existing, manifest))
+}
+
+/// Generates or updates a `manifest.webmanifest` in `out_dir` using provided priority level.
+///
+/// See [`build_manifest`] for how existing fields and metadata are merged.
+pub fn generate_manifest(
+    out_dir: &str,
+    priority: IconPriority,
+    metadata: &ManifestMetadata,
+    asset_refs: &AssetRefs,
+    progress: Option<&ProgressBar>,
+) -> Result<()> {
+    let (_, manifest) = build_manifest(out_dir, priority, metadata, asset_refs, progress)?;
 
     if let Some(pb) = progress {
         pb.set_message(format!(
@@ -113,7 +238,103 @@ pub fn generate_manifest(
     }
 
     let json = serde_json::to_string_pretty(&manifest)?;
-    fs::write(path, json)?;
+    fs::write(Path::new(out_dir).join(MANIFEST_FILE), json)?;
 
     Ok(())
 }
+
+/// Lists the manifest fields a run would change, without writing anything.
+///
+/// Icons are compared by `src`, so each added or removed icon is its own change.
+pub fn diff_manifest(
+    out_dir: &str,
+    priority: IconPriority,
+    metadata: &ManifestMetadata,
+    asset_refs: &AssetRefs,
+) -> Result<Vec<FieldChange>> {
+    let (existing, updated) = build_manifest(out_dir, priority, metadata, asset_refs, None)?;
+    let as_map = |manifest: Option<&Manifest>| -> Result<serde_json::Map<String, Value>> {
+        Ok(match manifest.map(serde_json::to_value).transpose()? {
+            Some(Value::Object(map)) => map,
+            _ => Default::default(),
+        })
+    };
+    let before = as_map(existing.as_ref())?;
+    let after = as_map(Some(&updated))?;
+
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    let mut changes = Vec::new();
+    for field in fields {
+        if field == "icons" {
+            let srcs = |map: &serde_json::Map<String, Value>| -> Vec<Value> {
+                map.get("icons")
+                    .and_then(Value::as_array)
+                    .map(|icons| icons.iter().filter_map(|i| i.get("src").cloned()).collect())
+                    .unwrap_or_default()
+            };
+            let (old, new) = (srcs(&before), srcs(&after));
+            for src in old.iter().filter(|src| !new.contains(src)) {
+                changes.push(FieldChange {
+                    field: field.clone(),
+                    before: Some(src.clone()),
+                    after: None,
+                });
+            }
+            for src in new.iter().filter(|src| !old.contains(src)) {
+                changes.push(FieldChange {
+                    field: field.clone(),
+                    before: None,
+                    after: Some(src.clone()),
+                });
+            }
+        } else if before.get(field) != after.get(field) {
+            changes.push(FieldChange {
+                field: field.clone(),
+                before: before.get(field).cloned(),
+                after: after.get(field).cloned(),
+            });
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    fn colors(theme: &str, background: &str) -> ManifestMetadata {
+        ManifestMetadata {
+            theme_color: Some(theme.to_string()),
+            background_color: Some(background.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rejects_an_invalid_background_color() {
+        let err = colors("teal", "#12345").validate().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("background color"), "{err}");
+    }
+
+    #[test]
+    fn only_the_theme_color_may_be_auto() {
+        assert!(colors("AUTO", "white").validate().is_ok());
+        assert!(colors("teal", "auto").validate().is_err());
+    }
+
+    #[test]
+    fn normalizes_named_and_hex_colors() {
+        let metadata = colors("Teal", " #FFF ").normalize_colors().unwrap();
+        assert_eq!(metadata.theme_color.as_deref(), Some("#008080"));
+        assert_eq!(metadata.background_color.as_deref(), Some("#ffffff"));
+
+        let metadata = colors("auto", "black").normalize_colors().unwrap();
+        assert_eq!(metadata.theme_color.as_deref(), Some("auto"));
+        assert!(metadata.wants_auto_theme_color());
+    }
+}
@@ -0,0 +1,154 @@
+//! How results reach the user: a spinner and colored text at a terminal, plain lines in
+//! pipes and CI logs, or a single JSON object per result with `--json`.
+
+use crate::error::FavisError;
+use crate::progress::create_spinner;
+use indicatif::ProgressBar;
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::io::{IsTerminal, Write};
+
+/// How much favis prints besides errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// JSON envelope shared by every result and error
+#[derive(Serialize)]
+struct Envelope<'a, T: Serialize> {
+    ok: bool,
+    command: &'a str,
+    #[serde(flatten)]
+    body: T,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a FavisError,
+}
+
+/// Output settings from the global flags and the terminal
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    /// Print results and errors as JSON on stdout
+    pub json: bool,
+    pub verbosity: Verbosity,
+    /// Whether stdout is a terminal
+    pub interactive: bool,
+}
+
+impl Output {
+    /// Resolves the output mode; turns colors off when stdout is not a terminal
+    pub fn new(json: bool, quiet: bool, verbose: u8) -> Self {
+        let interactive = std::io::stdout().is_terminal();
+        if !interactive || json {
+            anstream::ColorChoice::Never.write_global();
+        }
+        let verbosity = match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, _) => Verbosity::Verbose,
+        };
+        Self {
+            json,
+            verbosity,
+            interactive,
+        }
+    }
+
+    /// Whether plain status lines should be printed
+    fn chatty(&self) -> bool {
+        !self.json && self.verbosity > Verbosity::Quiet
+    }
+
+    /// Whether live progress is drawn (interactive terminal, not `--json` or `--quiet`)
+    pub fn shows_progress(&self) -> bool {
+        self.interactive && self.chatty()
+    }
+
+    /// A spinner at an interactive terminal; a hidden one for pipes, `--json` and `--quiet`
+    pub fn spinner(&self, msg: &str) -> ProgressBar {
+        if self.shows_progress() {
+            create_spinner(msg)
+        } else {
+            ProgressBar::hidden()
+        }
+    }
+
+    /// Shows a status update that should stay visible, e.g. in watch mode
+    pub fn status(&self, spinner: &ProgressBar, message: String) {
+        if !spinner.is_hidden() {
+            spinner.set_message(message);
+        } else if self.chatty() {
+            anstream::eprintln!("{message}");
+        }
+    }
+
+    /// Reports success and stops the spinner
+    pub fn finish(&self, spinner: &ProgressBar, message: String) {
+        if !spinner.is_hidden() {
+            spinner.finish_with_message(message);
+        } else if self.chatty() {
+            anstream::eprintln!("{message}");
+        }
+    }
+
+    /// Prints a warning unless it is reported in the JSON result instead
+    pub fn warn(&self, spinner: &ProgressBar, warning: &str) {
+        if self.chatty() {
+            spinner.suspend(|| anstream::eprintln!("{} {}", "Warning:".yellow().bold(), warning));
+        }
+    }
+
+    /// Prints a line only with `-v`
+    pub fn detail(&self, spinner: &ProgressBar, line: String) {
+        if !self.json && self.verbosity >= Verbosity::Verbose {
+            spinner.suspend(|| anstream::eprintln!("{line}"));
+        }
+    }
+
+    /// Prints a successful result as one line of JSON
+    pub fn result<T: Serialize>(&self, command: &str, body: &T) {
+        self.report(command, true, body);
+    }
+
+    /// Prints a result as one line of JSON, with `ok` set by the caller
+    pub fn report<T: Serialize>(&self, command: &str, ok: bool, body: &T) {
+        self.print_json(&Envelope { ok, command, body });
+    }
+
+    /// Prints an error as JSON or as the friendly message; errors show even with `--quiet`
+    pub fn error(&self, command: &str, err: &FavisError) {
+        if self.json {
+            self.print_json(&Envelope {
+                ok: false,
+                command,
+                body: ErrorBody { error: err },
+            });
+        } else {
+            err.display_friendly();
+        }
+    }
+
+    fn print_json<T: Serialize>(&self, value: &T) {
+        // A closed pipe (e.g. `| head`) is not worth a panic
+        match serde_json::to_string(value) {
+            Ok(json) => {
+                let _ = writeln!(std::io::stdout().lock(), "{json}");
+            }
+            Err(e) => eprintln!("Cannot serialize JSON output: {e}"),
+        }
+    }
+}
+
+/// Human-readable file size, e.g. `1.2 KB`
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
@@ -0,0 +1,199 @@
+//! Dry-run plans: what a command would write, without writing anything.
+
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// What a run would do to one file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanAction {
+    /// The file does not exist yet
+    Create,
+    /// The file exists and would be replaced
+    Overwrite,
+    /// The file is already up to date
+    Unchanged,
+    /// The file was edited by hand; the run would stop unless `--force` is given
+    Conflict,
+}
+
+impl PlanAction {
+    /// `Create` or `Overwrite`, depending on whether `path` exists
+    pub fn for_path(path: &Path) -> Self {
+        if path.exists() {
+            Self::Overwrite
+        } else {
+            Self::Create
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Overwrite => "overwrite",
+            Self::Unchanged => "unchanged",
+            Self::Conflict => "conflict",
+        }
+    }
+}
+
+/// One file in the plan
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanEntry {
+    /// File name relative to the output directory
+    pub file: String,
+    pub action: PlanAction,
+    /// Extra detail such as the sizes inside the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// A top-level manifest field that would change; `None` means absent
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Everything a run would do, for `--dry-run`
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    pub entries: Vec<PlanEntry>,
+    /// Changes to `manifest.webmanifest`, if the run would touch it
+    pub manifest_changes: Vec<FieldChange>,
+    /// Anything else worth knowing that is not a file
+    pub notes: Vec<String>,
+    /// Problems found while planning, e.g. files skipped by `--scan`
+    pub warnings: Vec<String>,
+}
+
+impl Plan {
+    /// Adds a file to the plan
+    pub fn push(&mut self, file: impl Into<String>, action: PlanAction, note: Option<String>) {
+        self.entries.push(PlanEntry {
+            file: file.into(),
+            action,
+            note,
+        });
+    }
+
+    fn count(&self, action: PlanAction) -> usize {
+        self.entries.iter().filter(|e| e.action == action).count()
+    }
+
+    /// One-line count of the files by action
+    fn summary(&self) -> String {
+        format!(
+            "{} to create, {} to overwrite, {} unchanged, {} in conflict",
+            self.count(PlanAction::Create),
+            self.count(PlanAction::Overwrite),
+            self.count(PlanAction::Unchanged),
+            self.count(PlanAction::Conflict)
+        )
+    }
+
+    /// Prints the plan to stdout
+    pub fn print(&self) {
+        anstream::println!(
+            "{} {}",
+            "Dry run:".cyan().bold(),
+            "nothing was written.".cyan()
+        );
+
+        let width = self.entries.iter().map(|e| e.file.len()).max().unwrap_or(0);
+        for entry in &self.entries {
+            let label = format!("{:<9}", entry.action.label());
+            let label = match entry.action {
+                PlanAction::Create => label.green().to_string(),
+                PlanAction::Overwrite => label.yellow().to_string(),
+                PlanAction::Unchanged => label.dimmed().to_string(),
+                PlanAction::Conflict => label.red().bold().to_string(),
+            };
+            match &entry.note {
+                Some(note) => anstream::println!(
+                    "  {label}  {:<width$}  {}",
+                    entry.file,
+                    format!("({note})").dimmed()
+                ),
+                None => anstream::println!("  {label}  {}", entry.file),
+            }
+        }
+
+        if !self.manifest_changes.is_empty() {
+            anstream::println!();
+            anstream::println!("{}", "Manifest changes:".cyan().bold());
+            for change in &self.manifest_changes {
+                match (&change.before, &change.after) {
+                    (None, Some(after)) => {
+                        anstream::println!("  {} {}: {after}", "+".green().bold(), change.field)
+                    }
+                    (Some(before), None) => {
+                        anstream::println!("  {} {}: {before}", "-".red().bold(), change.field)
+                    }
+                    (Some(before), Some(after)) => anstream::println!(
+                        "  {} {}: {before} → {after}",
+                        "~".yellow().bold(),
+                        change.field
+                    ),
+                    (None, None) => {}
+                }
+            }
+        }
+
+        for note in &self.notes {
+            anstream::println!("{}", note.dimmed());
+        }
+
+        if !self.entries.is_empty() {
+            anstream::println!();
+            anstream::println!("{}", self.summary().dimmed());
+        }
+        let conflicts = self.count(PlanAction::Conflict);
+        if conflicts > 0 {
+            anstream::println!(
+                "{} {conflicts} file(s) were edited by hand; the run would stop unless --force is given",
+                "Warning:".yellow().bold()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stage::TempDir;
+    use std::fs;
+
+    #[test]
+    fn classifies_files_by_whether_they_exist() {
+        let dir = TempDir::new("favis-plan-test");
+        fs::write(dir.path().join("favicon.ico"), b"old").unwrap();
+
+        assert_eq!(
+            PlanAction::for_path(&dir.path().join("favicon.ico")),
+            PlanAction::Overwrite
+        );
+        assert_eq!(
+            PlanAction::for_path(&dir.path().join("icon-32.png")),
+            PlanAction::Create
+        );
+    }
+
+    #[test]
+    fn summary_counts_every_state() {
+        let mut plan = Plan::default();
+        plan.push("icon-16.png", PlanAction::Create, None);
+        plan.push("icon-32.png", PlanAction::Create, None);
+        plan.push("favicon.ico", PlanAction::Overwrite, Some("16, 32".into()));
+        plan.push("apple-touch-icon.png", PlanAction::Unchanged, None);
+        plan.push("manifest.webmanifest", PlanAction::Conflict, None);
+
+        assert_eq!(
+            plan.summary(),
+            "2 to create, 1 to overwrite, 1 unchanged, 1 in conflict"
+        );
+    }
+}
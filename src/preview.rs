@@ -0,0 +1,261 @@
+//! `--preview`: a self-contained HTML contact sheet of the generated icons.
+
+use crate::error::{FavisError, Result};
+use crate::generate::GenerateReport;
+use crate::link::escape_attr;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ico::IconDir;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+/// File name of the contact sheet, written next to the icons
+pub const PREVIEW_FILE: &str = "favis-preview.html";
+
+/// Sizes tried, in order, for each mockup
+const TAB_SIZES: &[u32] = &[32, 16, 48];
+const IOS_SIZES: &[u32] = &[180, 152, 144, 120, 114, 76, 72, 57];
+const ANDROID_SIZES: &[u32] = &[512, 384, 192];
+
+/// One image on the sheet: a PNG, or one entry of the ICO
+struct Entry {
+    file: String,
+    size: u32,
+    /// `data:` URI, so the page works when attached or opened anywhere
+    uri: String,
+}
+
+/// Renders the contact sheet for the files in `report`, titled after the site
+pub fn render(report: &GenerateReport, title: Option<&str>) -> Result<String> {
+    let entries = entries(report)?;
+    let title = escape_attr(title.unwrap_or("Your site"));
+    let pick = |sizes: &[u32]| {
+        sizes
+            .iter()
+            .find_map(|&size| entries.iter().find(|entry| entry.size == size))
+            .or_else(|| entries.iter().max_by_key(|entry| entry.size))
+    };
+
+    let mut mocks = String::new();
+    if let Some(icon) = pick(TAB_SIZES) {
+        mocks.push_str(&tab_mock(icon, &title));
+    }
+    if let Some(icon) = pick(IOS_SIZES) {
+        mocks.push_str(&ios_mock(icon, &title));
+    }
+    if let Some(icon) = pick(ANDROID_SIZES) {
+        mocks.push_str(&android_mock(icon));
+    }
+
+    let rows: String = entries
+        .iter()
+        .map(|entry| {
+            let tile = |scale: u32| {
+                let side = entry.size * scale;
+                format!(r#"<div class="tile x{scale}" style="width:{side}px;height:{side}px"></div>"#)
+            };
+            let cell = |class: &str| format!(r#"<td class="{class}">{}{}</td>"#, tile(1), tile(2));
+            format!(
+                r#"<tr style="--icon:url({uri})"><th>{file}<small>{size}x{size}</small></th>{light}{dark}{checker}</tr>
+"#,
+                uri = entry.uri,
+                file = escape_attr(&entry.file),
+                size = entry.size,
+                light = cell("light"),
+                dark = cell("dark"),
+                checker = cell("checker"),
+            )
+        })
+        .collect();
+
+    Ok(format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Favicon preview · {title}</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<h1>Favicon preview</h1>
+<p class="meta">{title} · generated by favis {version}</p>
+<h2>In context</h2>
+<div class="mocks">
+{mocks}</div>
+<h2>Every icon at 1x and 2x</h2>
+<table>
+<thead><tr><th>File</th><th>Light</th><th>Dark</th><th>Transparent</th></tr></thead>
+<tbody>
+{rows}</tbody>
+</table>
+</body>
+</html>
+"#,
+        version = env!("CARGO_PKG_VERSION"),
+    ))
+}
+
+/// Writes the contact sheet into `dir`, leaving an identical file alone.
+///
+/// Returns whether the file was written.
+pub fn write(dir: &Path, html: &str) -> Result<bool> {
+    let path = dir.join(PREVIEW_FILE);
+    if fs::read(&path).is_ok_and(|old| old == html.as_bytes()) {
+        return Ok(false);
+    }
+    fs::write(&path, html).map_err(|err| {
+        FavisError::write_error(path.to_string_lossy().into_owned()).with_source(err)
+    })?;
+    Ok(true)
+}
+
+/// Every PNG in the report, smallest first, followed by the ICO's entries
+fn entries(report: &GenerateReport) -> Result<Vec<Entry>> {
+    let mut pngs = Vec::new();
+    let mut icos = Vec::new();
+    for file in &report.files {
+        let path = Path::new(&file.path);
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let lower = name.to_lowercase();
+        if !(lower.ends_with(".png") || lower.ends_with(".ico")) {
+            continue;
+        }
+        let bytes = fs::read(path)
+            .map_err(|err| FavisError::read_failed(path.display().to_string(), err))?;
+
+        if lower.ends_with(".png") {
+            pngs.push(Entry {
+                size: file.sizes.first().copied().unwrap_or_default(),
+                uri: data_uri(&bytes),
+                file: name,
+            });
+            continue;
+        }
+        let icon_dir = IconDir::read(Cursor::new(bytes)).map_err(|err| {
+            FavisError::processing_error(format!("Cannot read {name}")).with_source(err)
+        })?;
+        for entry in icon_dir.entries() {
+            let mut png = Vec::new();
+            entry
+                .decode()
+                .and_then(|image| image.write_png(&mut png))
+                .map_err(|err| {
+                    FavisError::processing_error(format!(
+                        "Cannot decode the {}x{} entry of {name}",
+                        entry.width(),
+                        entry.height()
+                    ))
+                    .with_source(err)
+                })?;
+            icos.push(Entry {
+                file: name.clone(),
+                size: entry.width(),
+                uri: data_uri(&png),
+            });
+        }
+    }
+    pngs.sort_by_key(|entry| entry.size);
+    icos.sort_by_key(|entry| entry.size);
+    pngs.extend(icos);
+    Ok(pngs)
+}
+
+fn data_uri(png: &[u8]) -> String {
+    format!("data:image/png;base64,{}", BASE64.encode(png))
+}
+
+/// Browser tabs in light and dark mode, with the icon at 16 CSS pixels
+fn tab_mock(icon: &Entry, title: &str) -> String {
+    let strip = |theme: &str| {
+        format!(
+            r#"<div class="tabs {theme}"><div class="tab active"><span class="favicon" style="background-image:url({uri})"></span><span class="label">{title}</span><span class="close">×</span></div><div class="tab"><span class="favicon blank"></span><span class="label">New Tab</span><span class="close">×</span></div></div>"#,
+            uri = icon.uri
+        )
+    };
+    format!(
+        "<figure>{}{}<figcaption>Browser tab ({}, {}x{})</figcaption></figure>\n",
+        strip("light"),
+        strip("dark"),
+        escape_attr(&icon.file),
+        icon.size,
+        icon.size
+    )
+}
+
+/// An iOS home screen; iOS rounds the corners and fills transparency with black
+fn ios_mock(icon: &Entry, title: &str) -> String {
+    let others: String = ["Photos", "Maps", "Notes"]
+        .iter()
+        .map(|name| {
+            format!(
+                r#"<div class="app"><span class="ios-icon other"></span><span>{name}</span></div>"#
+            )
+        })
+        .collect();
+    format!(
+        r#"<figure><div class="ios">{others}<div class="app"><span class="ios-icon" style="background-image:url({uri})"></span><span>{title}</span></div></div><figcaption>iOS home screen ({file}, {size}x{size})</figcaption></figure>
+"#,
+        uri = icon.uri,
+        file = escape_attr(&icon.file),
+        size = icon.size
+    )
+}
+
+/// The shapes Android launchers crop maskable icons to, plus the safe zone
+fn android_mock(icon: &Entry) -> String {
+    let shapes: String = ["circle", "squircle", "rounded", "safe-zone"]
+        .iter()
+        .map(|shape| {
+            format!(
+                r#"<span class="mask {shape}" title="{shape}" style="background-image:url({})"></span>"#,
+                icon.uri
+            )
+        })
+        .collect();
+    format!(
+        r#"<figure><div class="android">{shapes}</div><figcaption>Android maskable crops and safe zone ({}, {}x{})</figcaption></figure>
+"#,
+        escape_attr(&icon.file),
+        icon.size,
+        icon.size
+    )
+}
+
+const STYLE: &str = r#"
+body { font: 14px/1.4 system-ui, sans-serif; margin: 2rem; color: #1f2328; background: #fff; }
+h1 { margin: 0; } .meta { color: #59636e; margin-top: .25rem; }
+h2 { margin-top: 2rem; font-size: 1.1rem; }
+.mocks { display: flex; flex-wrap: wrap; gap: 2rem; align-items: flex-start; }
+figure { margin: 0; } figcaption { color: #59636e; font-size: 12px; margin-top: .5rem; }
+.tabs { display: flex; gap: 2px; padding: 6px 8px 0; width: 420px; border-radius: 8px 8px 0 0; }
+.tabs + .tabs { margin-top: 8px; }
+.tabs.light { background: #dee1e6; } .tabs.dark { background: #202124; }
+.tab { display: flex; align-items: center; gap: 8px; width: 180px; height: 34px; padding: 0 10px; border-radius: 8px 8px 0 0; font-size: 12px; }
+.tabs.light .tab { color: #3c4043; } .tabs.dark .tab { color: #e8eaed; }
+.tabs.light .tab.active { background: #fff; } .tabs.dark .tab.active { background: #35363a; }
+.tab .label { flex: 1; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+.favicon { width: 16px; height: 16px; background-size: 100% 100%; flex: none; }
+.favicon.blank { border-radius: 50%; background: #9aa0a6; }
+.ios { display: grid; grid-template-columns: repeat(4, 76px); gap: 18px 8px; padding: 24px 16px; border-radius: 24px; background: linear-gradient(160deg, #5b7cfa, #b05bd8); }
+.app { display: flex; flex-direction: column; align-items: center; gap: 4px; color: #fff; font-size: 11px; text-align: center; }
+.app span:last-child { width: 76px; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+.ios-icon { width: 60px; height: 60px; border-radius: 13.5px; background-color: #000; background-size: 100% 100%; }
+.ios-icon.other { background: rgba(255, 255, 255, .35); }
+.android { display: flex; gap: 16px; padding: 16px; border-radius: 16px; background: #e8eaed; }
+.mask { position: relative; width: 96px; height: 96px; background-color: #fff; background-size: 100% 100%; }
+.mask.circle { border-radius: 50%; } .mask.squircle { border-radius: 32%; } .mask.rounded { border-radius: 12%; }
+.mask.safe-zone::after { content: ""; position: absolute; inset: 10%; border: 1px dashed #d93025; border-radius: 50%; }
+table { border-collapse: collapse; }
+th, td { padding: 12px; border-bottom: 1px solid #d1d9e0; text-align: left; vertical-align: middle; }
+th small { display: block; color: #59636e; font-weight: normal; }
+td div { display: inline-block; vertical-align: middle; margin-right: 12px; }
+.tile { background-image: var(--icon); background-size: 100% 100%; }
+.tile.x2 { image-rendering: pixelated; }
+td.light { background: #fff; } td.dark { background: #202124; }
+td.checker { background: repeating-conic-gradient(#c8c8c8 0 25%, #fff 0 50%) 0 0 / 16px 16px; }
+"#;
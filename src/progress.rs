@@ -17,3 +17,17 @@ pub fn create_spinner(msg: &str) -> ProgressBar {
     pb.enable_steady_tick(Duration::from_millis(60));
     pb
 }
+
+/// Creates a spinner for one job of a batch, labelled with `prefix`
+pub fn create_job_spinner(prefix: &str) -> ProgressBar {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ")
+            .template("{spinner:.green} {prefix:.bold} {msg}")
+            .expect("Valid progress bar template"),
+    );
+    pb.set_prefix(prefix.to_string());
+    pb.set_message(format!("{}", "Waiting...".dimmed()));
+    pb
+}
@@ -0,0 +1,375 @@
+//! Resizing with a choice of filter per size range, plus unsharp masking for small icons.
+//!
+//! The image crate has no Mitchell or box (area averaging) filter, and it filters straight,
+//! gamma-encoded values, so resizing is done here with a separable convolution on
+//! premultiplied linear-light RGBA.
+
+use crate::cli::ResampleFilter;
+use image::{DynamicImage, RgbaImage};
+use serde::Serialize;
+use std::f32::consts::PI;
+use std::sync::OnceLock;
+
+/// Largest size, in pixels, that counts as small: it uses the small filter and can be sharpened
+pub const SMALL_MAX: u32 = 48;
+
+/// Bumped when resizing changes its output, so lock files see old icons as stale
+pub const RESAMPLER_VERSION: u32 = 2;
+
+/// Gaussian radius of the unsharp mask, in output pixels
+const SHARPEN_SIGMA: f32 = 0.6;
+/// How much of the detail the unsharp mask adds back
+const SHARPEN_AMOUNT: f32 = 0.6;
+/// Differences below this are left alone, so flat areas don't turn grainy
+const SHARPEN_THRESHOLD: f32 = 2.0 / 255.0;
+
+/// How icons are scaled down from the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Resampling {
+    /// Filter for sizes above [`SMALL_MAX`]
+    pub filter: ResampleFilter,
+    /// Filter for sizes up to [`SMALL_MAX`]
+    pub small_filter: ResampleFilter,
+    /// Apply an unsharp mask to sizes up to [`SMALL_MAX`]
+    pub sharpen: bool,
+}
+
+impl Default for Resampling {
+    fn default() -> Self {
+        Self {
+            filter: ResampleFilter::Lanczos3,
+            small_filter: ResampleFilter::Lanczos3,
+            sharpen: false,
+        }
+    }
+}
+
+impl Resampling {
+    /// Scales `source` to a `size`×`size` icon with the filter (and sharpening) for that size
+    pub fn render(&self, source: &Pixels, size: u32) -> RgbaImage {
+        let small = size <= SMALL_MAX;
+        let filter = if small {
+            self.small_filter
+        } else {
+            self.filter
+        };
+        let mut pixels = source.resize(size, size, filter);
+        if small && self.sharpen {
+            pixels = pixels.sharpen();
+        }
+        pixels.into_image()
+    }
+}
+
+impl ResampleFilter {
+    /// Kernel radius in source pixels at 1:1 scale
+    fn support(self) -> f32 {
+        match self {
+            ResampleFilter::Lanczos3 => 3.0,
+            ResampleFilter::CatmullRom | ResampleFilter::Mitchell => 2.0,
+            ResampleFilter::Box => 0.5,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        match self {
+            ResampleFilter::Lanczos3 => lanczos(x, 3.0),
+            ResampleFilter::CatmullRom => cubic(x, 0.0, 0.5),
+            ResampleFilter::Mitchell => cubic(x, 1.0 / 3.0, 1.0 / 3.0),
+            ResampleFilter::Box => {
+                if x.abs() <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+fn lanczos(x: f32, a: f32) -> f32 {
+    if x.abs() < a {
+        sinc(x) * sinc(x / a)
+    } else {
+        0.0
+    }
+}
+
+/// Mitchell–Netravali cubic with parameters `b` and `c`
+fn cubic(x: f32, b: f32, c: f32) -> f32 {
+    let x = x.abs();
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b)
+    } else if x < 2.0 {
+        (-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        0.0
+    };
+    value / 6.0
+}
+
+/// For each output position along one axis: the first source index and its weights
+fn contributions(
+    src_len: u32,
+    dst_len: u32,
+    support: f32,
+    weight: impl Fn(f32) -> f32,
+) -> Vec<(usize, Vec<f32>)> {
+    let scale = src_len as f32 / dst_len as f32;
+    // When shrinking, widen the kernel so every source pixel contributes (area coverage)
+    let filter_scale = scale.max(1.0);
+    let radius = support * filter_scale;
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = (center - radius).floor().max(0.0) as usize;
+            let end = ((center + radius).ceil() as usize).min(src_len as usize);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| weight((j as f32 + 0.5 - center) / filter_scale))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum != 0.0 {
+                weights.iter_mut().for_each(|w| *w /= sum);
+            }
+            (start, weights)
+        })
+        .collect()
+}
+
+/// An image as premultiplied, linear-light float RGBA in 0..1, row by row.
+///
+/// Filtering gamma-encoded or straight-alpha values darkens anti-aliased edges: transparent
+/// pixels (usually stored as black) get mixed into the color. Premultiplied linear light
+/// averages what is actually visible.
+///
+/// Converting takes 16 bytes per pixel, so convert a source once and render every size
+/// from the result.
+#[derive(Clone)]
+pub struct Pixels {
+    width: u32,
+    height: u32,
+    data: Vec<[f32; 4]>,
+}
+
+/// sRGB-encoded byte to linear light
+fn srgb_to_linear(value: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let v = i as f32 / 255.0;
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    })[value as usize]
+}
+
+/// Linear light to an sRGB-encoded value in 0..1
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Pixels {
+    /// Converts `img`, without an intermediate RGBA8 copy when it already is one
+    pub fn from_dynamic(img: &DynamicImage) -> Self {
+        match img.as_rgba8() {
+            Some(rgba) => Self::from_image(rgba),
+            None => Self::from_image(&img.to_rgba8()),
+        }
+    }
+
+    pub fn from_image(img: &RgbaImage) -> Self {
+        Self {
+            width: img.width(),
+            height: img.height(),
+            data: img
+                .pixels()
+                .map(|p| {
+                    let [r, g, b, a] = p.0;
+                    let alpha = a as f32 / 255.0;
+                    [
+                        srgb_to_linear(r) * alpha,
+                        srgb_to_linear(g) * alpha,
+                        srgb_to_linear(b) * alpha,
+                        alpha,
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn into_image(self) -> RgbaImage {
+        let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let raw = self
+            .data
+            .iter()
+            .flat_map(|&[r, g, b, a]| {
+                if a <= 0.0 {
+                    return [0; 4];
+                }
+                let encode = |c: f32| to_byte(linear_to_srgb(c / a));
+                [encode(r), encode(g), encode(b), to_byte(a)]
+            })
+            .collect();
+        RgbaImage::from_raw(self.width, self.height, raw).expect("buffer matches dimensions")
+    }
+
+    fn resize(&self, width: u32, height: u32, filter: ResampleFilter) -> Self {
+        let weight = |x| filter.weight(x);
+        let mut resized = self
+            .convolve_rows(width, filter.support(), weight)
+            .convolve_columns(height, filter.support(), weight);
+        // Negative lobes can overshoot; keep alpha in range and color within alpha
+        for pixel in &mut resized.data {
+            let alpha = pixel[3].clamp(0.0, 1.0);
+            *pixel = [
+                pixel[0].clamp(0.0, alpha),
+                pixel[1].clamp(0.0, alpha),
+                pixel[2].clamp(0.0, alpha),
+                alpha,
+            ];
+        }
+        resized
+    }
+
+    /// Resamples every row to `width`
+    fn convolve_rows(&self, width: u32, support: f32, weight: impl Fn(f32) -> f32) -> Self {
+        let table = contributions(self.width, width, support, weight);
+        let mut data = Vec::with_capacity((width * self.height) as usize);
+        for row in self.data.chunks(self.width as usize) {
+            data.extend(table.iter().map(|(start, weights)| {
+                weighted_sum(weights.iter().zip(&row[*start..]).map(|(w, p)| (*w, p)))
+            }));
+        }
+        Self {
+            width,
+            height: self.height,
+            data,
+        }
+    }
+
+    /// Resamples every column to `height`
+    fn convolve_columns(&self, height: u32, support: f32, weight: impl Fn(f32) -> f32) -> Self {
+        let table = contributions(self.height, height, support, weight);
+        let width = self.width as usize;
+        let mut data = Vec::with_capacity(width * height as usize);
+        for (start, weights) in &table {
+            for x in 0..width {
+                data.push(weighted_sum(
+                    weights
+                        .iter()
+                        .enumerate()
+                        .map(|(k, w)| (*w, &self.data[(start + k) * width + x])),
+                ));
+            }
+        }
+        Self {
+            width: self.width,
+            height,
+            data,
+        }
+    }
+
+    /// Unsharp mask on the color channels; alpha is kept as is so edges don't grow halos
+    fn sharpen(self) -> Self {
+        let gaussian = |x: f32| (-x * x / (2.0 * SHARPEN_SIGMA * SHARPEN_SIGMA)).exp();
+        let support = 3.0 * SHARPEN_SIGMA;
+        let blurred = self
+            .convolve_rows(self.width, support, gaussian)
+            .convolve_columns(self.height, support, gaussian);
+
+        let data = self
+            .data
+            .iter()
+            .zip(&blurred.data)
+            .map(|(pixel, blur)| {
+                let alpha = pixel[3];
+                let mut out = *pixel;
+                for c in 0..3 {
+                    let detail = pixel[c] - blur[c];
+                    if detail.abs() >= SHARPEN_THRESHOLD {
+                        out[c] = (pixel[c] + SHARPEN_AMOUNT * detail).clamp(0.0, alpha);
+                    }
+                }
+                out
+            })
+            .collect();
+        Self { data, ..self }
+    }
+}
+
+fn weighted_sum<'a>(taps: impl Iterator<Item = (f32, &'a [f32; 4])>) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+    for (weight, pixel) in taps {
+        for c in 0..4 {
+            sum[c] += weight * pixel[c];
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg::PixmapExt;
+    use resvg::tiny_skia::{Pixmap, PremultipliedColorU8};
+
+    /// A white shape whose anti-aliased edge fades out over transparent black must stay
+    /// white after demultiplying and downscaling, not turn into a dark fringe.
+    #[test]
+    fn semi_transparent_edges_keep_their_color() {
+        // Each row: opaque white, opaque white, white at 50% coverage, transparent
+        let mut pixmap = Pixmap::new(4, 4).unwrap();
+        let row = [
+            PremultipliedColorU8::from_rgba(255, 255, 255, 255).unwrap(),
+            PremultipliedColorU8::from_rgba(255, 255, 255, 255).unwrap(),
+            PremultipliedColorU8::from_rgba(128, 128, 128, 128).unwrap(),
+            PremultipliedColorU8::TRANSPARENT,
+        ];
+        for (i, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+            *pixel = row[i % 4];
+        }
+
+        let image = pixmap.to_dynamic_image().unwrap().to_rgba8();
+        assert_eq!(image.get_pixel(2, 0).0, [255, 255, 255, 128]);
+
+        let resampling = Resampling {
+            filter: ResampleFilter::Box,
+            small_filter: ResampleFilter::Box,
+            sharpen: false,
+        };
+        let small = resampling.render(&Pixels::from_image(&image), 2);
+        assert_eq!(small.get_pixel(0, 0).0, [255, 255, 255, 255]);
+        let [r, g, b, a] = small.get_pixel(1, 1).0;
+        assert_eq!([r, g, b], [255, 255, 255]);
+        assert_eq!(a, 64);
+    }
+}
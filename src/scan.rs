@@ -0,0 +1,235 @@
+//! Discover favicon files in a directory by name and decoded dimensions.
+
+use crate::error::{FavisError, Result};
+use crate::icon_sizes::get_all_sizes;
+use crate::img;
+use crate::limits::Limits;
+use crate::link::IconEntry;
+use std::fs;
+use std::path::Path;
+
+/// File name fragments that mark an image as a favicon candidate
+const ICON_NAME_HINTS: &[&str] = &["icon", "android-chrome", "mstile"];
+
+/// Result of scanning a directory for favicon files
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    /// Icons that matched a known size, in file name order
+    pub icons: Vec<IconEntry>,
+    /// File name of a webmanifest found in the directory, if any
+    pub manifest: Option<String>,
+    /// Human-readable reasons for files that were skipped
+    pub warnings: Vec<String>,
+}
+
+/// Returns true if the lowercase file stem looks like a favicon asset
+fn is_icon_name(stem: &str) -> bool {
+    ICON_NAME_HINTS.iter().any(|hint| stem.contains(hint))
+}
+
+/// Parses an `NxN` size embedded in a file name, e.g. `favicon-32x32`
+fn size_from_name(stem: &str) -> Option<u32> {
+    stem.split(['-', '_', '.'])
+        .filter_map(|part| part.split_once('x'))
+        .find_map(|(w, h)| match (w.parse::<u32>(), h.parse::<u32>()) {
+            (Ok(w), Ok(h)) if w == h => Some(w),
+            _ => None,
+        })
+}
+
+/// Reads the sizes of all images stored in an ICO file, refusing entries over `limits`
+fn ico_sizes(path: &Path, limits: &Limits) -> Result<Vec<u32>> {
+    let data = fs::read(path)?;
+    // Decode each entry instead of trusting the directory header
+    let images = img::decode_ico(&data, &path.display().to_string(), limits)?;
+
+    let mut sizes = Vec::new();
+    for image in images {
+        if image.width() == image.height() && !sizes.contains(&image.width()) {
+            sizes.push(image.width());
+        }
+    }
+    sizes.sort_unstable();
+    Ok(sizes)
+}
+
+/// Scans `dir` (non-recursively) and returns icon entries for every recognized favicon
+///
+/// PNGs must be square and match a size from `icon_sizes::get_all_sizes`. ICO files are
+/// listed with all of their embedded sizes, and SVG favicons are listed with `sizes="any"`.
+/// ICO entries over `limits` are skipped with a warning.
+pub fn scan_directory(dir: &str, limits: &Limits) -> Result<ScanResult> {
+    let path = Path::new(dir);
+    if !path.is_dir() {
+        return Err(FavisError::file_not_found(format!(
+            "Not a directory: {dir}"
+        )));
+    }
+
+    let known_sizes: Vec<u32> = get_all_sizes().iter().map(|s| s.size).collect();
+
+    let mut names: Vec<String> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+
+    let mut result = ScanResult::default();
+
+    for name in names {
+        let file_path = path.join(&name);
+        let lower = name.to_lowercase();
+        let stem = lower.rsplit_once('.').map_or(lower.as_str(), |(s, _)| s);
+
+        if lower.ends_with(".webmanifest") || lower == "manifest.json" {
+            result.manifest.get_or_insert(name);
+            continue;
+        }
+        if !is_icon_name(stem) {
+            continue;
+        }
+
+        if lower.ends_with(".ico") {
+            match ico_sizes(&file_path, limits) {
+                Ok(sizes) if !sizes.is_empty() => {
+                    let sizes: Vec<String> = sizes.iter().map(|s| format!("{s}x{s}")).collect();
+                    result.icons.push(IconEntry {
+                        src: name,
+                        sizes: Some(sizes.join(" ")),
+                        mime_type: Some("image/x-icon".into()),
+                        purpose: None,
+                    });
+                }
+                Ok(_) => result
+                    .warnings
+                    .push(format!("{name}: ICO contains no square images")),
+                Err(e) => result.warnings.push(format!("{name}: {e}")),
+            }
+        } else if lower.ends_with(".svg") {
+            result.icons.push(IconEntry {
+                src: name,
+                sizes: Some("any".into()),
+                mime_type: Some("image/svg+xml".into()),
+                purpose: None,
+            });
+        } else if lower.ends_with(".png") {
+            let (width, height) = match image::image_dimensions(&file_path) {
+                Ok(dims) => dims,
+                Err(e) => {
+                    result.warnings.push(format!("{name}: {e}"));
+                    continue;
+                }
+            };
+            if width != height {
+                result
+                    .warnings
+                    .push(format!("{name}: not square ({width}x{height})"));
+                continue;
+            }
+            if let Some(named) = size_from_name(stem) {
+                if named != width {
+                    result.warnings.push(format!(
+                        "{name}: named {named}x{named} but is actually {width}x{width}"
+                    ));
+                }
+            }
+            if !known_sizes.contains(&width) {
+                result.warnings.push(format!(
+                    "{name}: {width}x{width} is not a known favicon size"
+                ));
+                continue;
+            }
+            result.icons.push(IconEntry {
+                src: name,
+                sizes: Some(format!("{width}x{width}")),
+                mime_type: Some("image/png".into()),
+                purpose: None,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stage::TempDir;
+    use ico::{IconDir, IconDirEntry, IconImage, ResourceType};
+    use image::RgbaImage;
+
+    fn png(dir: &Path, name: &str, width: u32, height: u32) {
+        RgbaImage::new(width, height).save(dir.join(name)).unwrap();
+    }
+
+    fn ico(dir: &Path, name: &str, sizes: &[u32]) {
+        let mut icon_dir = IconDir::new(ResourceType::Icon);
+        for &size in sizes {
+            let image = IconImage::from_rgba_data(size, size, vec![0; (size * size * 4) as usize]);
+            icon_dir.add_entry(IconDirEntry::encode(&image).unwrap());
+        }
+        icon_dir
+            .write(fs::File::create(dir.join(name)).unwrap())
+            .unwrap();
+    }
+
+    fn listed(result: &ScanResult) -> Vec<(&str, &str)> {
+        result
+            .icons
+            .iter()
+            .map(|icon| (icon.src.as_str(), icon.sizes.as_deref().unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_manifest_and_icons_by_name_and_decoded_size() {
+        let dir = TempDir::new("scan");
+        fs::write(dir.path().join("site.webmanifest"), "{}").unwrap();
+        png(dir.path(), "favicon-32x32.png", 32, 32);
+        png(dir.path(), "apple-touch-icon.png", 180, 180);
+        ico(dir.path(), "favicon.ico", &[32, 16, 32]);
+        fs::write(dir.path().join("icon.svg"), "<svg/>").unwrap();
+        png(dir.path(), "logo.png", 32, 32);
+
+        let result = scan_directory(&dir.path_str(), &Limits::default()).unwrap();
+        assert_eq!(result.manifest.as_deref(), Some("site.webmanifest"));
+        assert_eq!(
+            listed(&result),
+            [
+                ("apple-touch-icon.png", "180x180"),
+                ("favicon-32x32.png", "32x32"),
+                ("favicon.ico", "16x16 32x32"),
+                ("icon.svg", "any"),
+            ]
+        );
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn warns_about_icons_it_cannot_use() {
+        let dir = TempDir::new("scan");
+        png(dir.path(), "favicon-16x16.png", 32, 32);
+        png(dir.path(), "icon-wide.png", 64, 32);
+        png(dir.path(), "icon-odd.png", 33, 33);
+        ico(dir.path(), "favicon.ico", &[16, 32]);
+
+        let limits = Limits {
+            max_dimension: 16,
+            ..Default::default()
+        };
+        let result = scan_directory(&dir.path_str(), &limits).unwrap();
+        // The misnamed PNG is still usable at its real size
+        assert_eq!(listed(&result), [("favicon-16x16.png", "32x32")]);
+        let warnings = result.warnings.join("\n");
+        for expected in [
+            "favicon-16x16.png: named 16x16 but is actually 32x32",
+            "favicon.ico: ",
+            "--max-dimension",
+            "icon-odd.png: 33x33 is not a known favicon size",
+            "icon-wide.png: not square (64x32)",
+        ] {
+            assert!(warnings.contains(expected), "{expected:?} in {warnings}");
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Transactional output writes: stage every file, then move them into place together.
+
+use crate::error::{FavisError, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Name prefix of the staging directory created inside the output directory
+const STAGING_PREFIX: &str = ".favis-staging-";
+
+/// Counts scratch directories created by this process, so each gets its own name
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// `<pid>-<n>`: unique among the scratch directories of every running favis process, so
+/// concurrent runs (e.g. batch jobs) never share or delete each other's files
+pub fn unique_suffix() -> String {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{id}", std::process::id())
+}
+
+/// A staging directory for one run.
+///
+/// Files are written into [`Staging::dir`] and only moved into the output directory by
+/// [`Staging::commit`]. Dropping an uncommitted staging area (on an error or cancellation)
+/// deletes it and leaves the output directory exactly as it was.
+#[derive(Debug)]
+pub struct Staging {
+    out_dir: PathBuf,
+    dir: PathBuf,
+    /// Files in the output directory to delete on commit, e.g. stale fingerprinted copies
+    removals: Vec<PathBuf>,
+}
+
+/// What commit did to one path, so it can be undone
+enum Step {
+    /// A new file was moved in where nothing existed
+    Created(PathBuf),
+    /// An existing file was moved to the backup path (and maybe replaced)
+    BackedUp { target: PathBuf, backup: PathBuf },
+}
+
+impl Staging {
+    /// Creates the output directory if needed and an empty staging directory inside it
+    pub fn new(out_dir: &str) -> Result<Self> {
+        let out_dir = PathBuf::from(out_dir);
+        let dir = out_dir.join(format!("{STAGING_PREFIX}{}", unique_suffix()));
+
+        // A crashed process with the same pid may have left one behind
+        if dir.exists() {
+            let _ = fs::remove_dir_all(&dir);
+        }
+        fs::create_dir_all(&dir).map_err(|err| {
+            FavisError::write_error(format!(
+                "Cannot create staging directory: {}",
+                dir.display()
+            ))
+            .with_source(err)
+        })?;
+
+        Ok(Self {
+            out_dir,
+            dir,
+            removals: Vec::new(),
+        })
+    }
+
+    /// Directory new files should be written to
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The staging directory as a string, for APIs that take `&str` paths
+    pub fn dir_str(&self) -> String {
+        self.dir.to_string_lossy().into_owned()
+    }
+
+    /// The real output directory
+    pub fn out_dir(&self) -> &Path {
+        &self.out_dir
+    }
+
+    /// Copies an existing output file into staging so it can be updated in place there
+    pub fn copy_in(&self, name: &str) -> Result<()> {
+        let source = self.out_dir.join(name);
+        if source.exists() {
+            fs::copy(&source, self.dir.join(name)).map_err(|err| {
+                FavisError::write_error(format!("Cannot stage {}", source.display()))
+                    .with_source(err)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Schedules a file in the output directory for deletion when the run commits
+    pub fn remove_on_commit(&mut self, path: PathBuf) {
+        self.removals.push(path);
+    }
+
+    /// Moves every staged file into the output directory.
+    ///
+    /// Existing files are moved aside first, so if any step fails everything done so far is
+    /// undone and the previous outputs are restored.
+    pub fn commit(self) -> Result<()> {
+        let backup_dir = self.dir.join(".backup");
+        fs::create_dir_all(&backup_dir)?;
+
+        let mut staged: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        staged.sort();
+
+        let mut steps = Vec::new();
+        if let Err(err) = self.apply(&staged, &backup_dir, &mut steps) {
+            Self::rollback(steps);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn apply(&self, staged: &[PathBuf], backup_dir: &Path, steps: &mut Vec<Step>) -> Result<()> {
+        let move_aside = |target: &Path, steps: &mut Vec<Step>| -> Result<()> {
+            if target.exists() {
+                let backup = backup_dir.join(steps.len().to_string());
+                fs::rename(target, &backup).map_err(|err| {
+                    FavisError::write_error(format!("Cannot replace {}", target.display()))
+                        .with_source(err)
+                })?;
+                steps.push(Step::BackedUp {
+                    target: target.to_path_buf(),
+                    backup,
+                });
+            }
+            Ok(())
+        };
+
+        for path in &self.removals {
+            move_aside(path, steps)?;
+        }
+        for source in staged {
+            let Some(name) = source.file_name() else {
+                continue;
+            };
+            let target = self.out_dir.join(name);
+            let existed = target.exists();
+            move_aside(&target, steps)?;
+            fs::rename(source, &target).map_err(|err| {
+                FavisError::write_error(format!("Cannot move {} into place", target.display()))
+                    .with_source(err)
+            })?;
+            if !existed {
+                steps.push(Step::Created(target));
+            }
+        }
+        Ok(())
+    }
+
+    /// Undoes commit steps in reverse order; best effort, since we are already failing
+    fn rollback(steps: Vec<Step>) {
+        for step in steps.into_iter().rev() {
+            match step {
+                Step::Created(target) => {
+                    let _ = fs::remove_file(target);
+                }
+                Step::BackedUp { target, backup } => {
+                    let _ = fs::remove_file(&target);
+                    let _ = fs::rename(backup, target);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        // Committed files have been moved out, so this only removes leftovers and backups
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A scratch directory for tests, deleted when dropped, even if an assert panicked
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Creates `<temp>/favis-<name>-<unique suffix>`
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("favis-{name}-{}", unique_suffix()));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// The directory as a string, for APIs that take `&str` paths
+    pub fn path_str(&self) -> String {
+        self.0.to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(dir: &Path) -> Vec<(String, String)> {
+        let mut files: Vec<(String, String)> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, fs::read_to_string(&path).unwrap_or_default())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn commit_moves_staged_files_into_place() {
+        let out = TempDir::new("stage");
+        fs::write(out.path().join("a.png"), "old a").unwrap();
+        fs::write(out.path().join("stale.png"), "stale").unwrap();
+
+        let mut staging = Staging::new(&out.path_str()).unwrap();
+        fs::write(staging.dir().join("a.png"), "new a").unwrap();
+        fs::write(staging.dir().join("b.png"), "new b").unwrap();
+        staging.remove_on_commit(out.path().join("stale.png"));
+        staging.commit().unwrap();
+
+        assert_eq!(
+            contents(out.path()),
+            [
+                ("a.png".into(), "new a".into()),
+                ("b.png".into(), "new b".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn a_failed_commit_restores_the_previous_files() {
+        let out = TempDir::new("stage");
+        for name in ["a.png", "b.png", "stale.png"] {
+            fs::write(out.path().join(name), format!("old {name}")).unwrap();
+        }
+        let before = contents(out.path());
+
+        let mut staging = Staging::new(&out.path_str()).unwrap();
+        for name in ["a.png", "aa-new.png", "b.png"] {
+            fs::write(staging.dir().join(name), format!("new {name}")).unwrap();
+        }
+        staging.remove_on_commit(out.path().join("stale.png"));
+        // Steps so far: stale.png backed up, a.png backed up, aa-new.png created. A non-empty
+        // directory where b.png's backup goes makes that fourth rename fail.
+        let blocker = staging.dir().join(".backup").join("3");
+        fs::create_dir_all(&blocker).unwrap();
+        fs::write(blocker.join("file"), "").unwrap();
+
+        let err = staging.commit().unwrap_err();
+        assert!(err.context.contains("b.png"), "{}", err.context);
+        // Dropping the staging area also removed the staging and backup directories
+        assert_eq!(contents(out.path()), before);
+    }
+}
@@ -0,0 +1,51 @@
+//! `-` as a path: read inputs from stdin and stream outputs to stdout.
+
+use crate::error::{FavisError, Result};
+use std::io::{self, IsTerminal, Read};
+
+/// Path that stands for stdin or stdout
+pub const STDIO_PATH: &str = "-";
+
+/// Whether `path` means stdin or stdout
+pub fn is_stdio(path: &str) -> bool {
+    path == STDIO_PATH
+}
+
+/// How to name `path` in messages
+pub fn label(path: &str) -> &str {
+    if is_stdio(path) {
+        "stdin"
+    } else {
+        path
+    }
+}
+
+/// Reads all of stdin, refusing to wait on an interactive terminal
+pub fn read_stdin(what: &str) -> Result<Vec<u8>> {
+    let mut stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Err(FavisError::invalid_option(format!(
+            "Expected {what} on stdin, but nothing was piped in"
+        )));
+    }
+    let mut data = Vec::new();
+    stdin
+        .read_to_end(&mut data)
+        .map_err(|err| FavisError::read_failed(format!("{what} from stdin"), err))?;
+    if data.is_empty() {
+        return Err(FavisError::invalid_option(format!(
+            "Expected {what} on stdin, but it was empty"
+        )));
+    }
+    Ok(data)
+}
+
+/// Checks that binary output is not about to be dumped on a terminal
+pub fn ensure_stdout_piped(what: &str) -> Result<()> {
+    if io::stdout().is_terminal() {
+        return Err(FavisError::invalid_option(format!(
+            "Refusing to write {what} to the terminal; pipe it into another command or redirect it to a file"
+        )));
+    }
+    Ok(())
+}
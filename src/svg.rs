@@ -1,25 +1,444 @@
 //! SVG rendering to PNG using resvg.
 
+use crate::cli::Fit;
+use crate::color::validate_color;
 use crate::error::{FavisError, Result};
+use crate::fit;
+use crate::limits::Limits;
 use indicatif::ProgressBar;
 use owo_colors::OwoColorize;
 use resvg::tiny_skia::Pixmap;
-use resvg::usvg::{self, Tree};
+use resvg::usvg::{self, roxmltree, Tree};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-/// Render SVG data to a pixmap at the specified size.
-pub fn render_svg(
-    svg_data: &[u8],
-    width: u32,
-    height: u32,
-    progress: Option<&ProgressBar>,
-) -> Result<Pixmap> {
-    if let Some(pb) = progress {
-        pb.set_message(format!("{}", "Parsing SVG data...".cyan().bold()));
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+
+/// Resolution SVG physical units (`mm`, `in`, `pt`) are converted at by default
+pub const DEFAULT_DPI: f32 = 96.0;
+
+/// Fonts tried, in order, for the generic `serif`, `sans-serif` and `monospace` families
+const SERIF_FONTS: &[&str] = &[
+    "Times New Roman",
+    "Liberation Serif",
+    "DejaVu Serif",
+    "Noto Serif",
+];
+const SANS_SERIF_FONTS: &[&str] = &[
+    "Arial",
+    "Helvetica",
+    "Liberation Sans",
+    "DejaVu Sans",
+    "Noto Sans",
+];
+const MONOSPACE_FONTS: &[&str] = &[
+    "Courier New",
+    "Liberation Mono",
+    "DejaVu Sans Mono",
+    "Noto Sans Mono",
+];
+
+/// A crop in SVG user units that replaces the root element's `viewBox`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ViewBox {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl FromStr for ViewBox {
+    type Err = String;
+
+    /// Parses `x,y,width,height`; spaces work as separators too, as in SVG
+    fn from_str(value: &str) -> std::result::Result<Self, String> {
+        let numbers = value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(str::parse::<f32>)
+            .collect::<std::result::Result<Vec<_>, _>>();
+        let Ok(&[x, y, width, height]) = numbers.as_deref() else {
+            return Err(format!(
+                "expected four numbers as x,y,width,height, got \"{value}\""
+            ));
+        };
+        if ![x, y, width, height].iter().all(|n| n.is_finite()) || width <= 0.0 || height <= 0.0 {
+            return Err(format!(
+                "the width and height of a viewBox must be positive, got \"{value}\""
+            ));
+        }
+        Ok(Self {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+impl TryFrom<String> for ViewBox {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, String> {
+        value.parse()
+    }
+}
+
+/// How SVG sources are parsed: fonts, linked files, CSS and units
+#[derive(Debug, Clone, Serialize)]
+pub struct SvgOptions {
+    /// Font directories loaded on top of the system fonts
+    pub font_dirs: Vec<PathBuf>,
+    /// Directory relative `<image href>`s resolve against, normally the source's directory
+    #[serde(skip)]
+    pub resources_dir: Option<PathBuf>,
+    /// User stylesheet (CSS text) applied on top of the SVG's own styles
+    pub stylesheet: Option<String>,
+    /// Color that `currentColor` resolves to
+    pub current_color: Option<String>,
+    /// Resolution for physical units
+    pub dpi: f32,
+    /// Render only the element with this id, cropped to its bounds
+    pub id: Option<String>,
+    /// Crop that replaces the SVG's own `viewBox`
+    pub viewbox: Option<ViewBox>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            font_dirs: Vec::new(),
+            resources_dir: None,
+            stylesheet: None,
+            current_color: None,
+            dpi: DEFAULT_DPI,
+            id: None,
+            viewbox: None,
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Checks values that come from flags or a config file
+    pub fn validate(&self) -> Result<()> {
+        if let Some(color) = &self.current_color {
+            validate_color("current color", color)?;
+        }
+        if !(self.dpi.is_finite() && self.dpi > 0.0) {
+            return Err(FavisError::invalid_option(format!(
+                "DPI must be a positive number, got {}",
+                self.dpi
+            )));
+        }
+        for dir in &self.font_dirs {
+            if !dir.is_dir() {
+                return Err(FavisError::file_not_found(format!(
+                    "Font directory not found: {}",
+                    dir.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds usvg options; fonts are only loaded when the SVG has text to shape
+    fn usvg_options(&self, svg_data: &[u8]) -> usvg::Options<'static> {
+        let mut opt = usvg::Options {
+            resources_dir: self.resources_dir.clone(),
+            dpi: self.dpi,
+            ..Default::default()
+        };
+
+        // `currentColor` follows the CSS `color` property of the root element
+        let current_color = self
+            .current_color
+            .as_ref()
+            .map(|color| format!("svg {{ color: {color}; }}\n"));
+        let stylesheet = [current_color.as_deref(), self.stylesheet.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<String>();
+        if !stylesheet.is_empty() {
+            opt.style_sheet = Some(stylesheet);
+        }
+
+        if has_text(svg_data) {
+            let fontdb = opt.fontdb_mut();
+            fontdb.load_system_fonts();
+            for dir in &self.font_dirs {
+                fontdb.load_fonts_dir(dir);
+            }
+
+            // Generic families default to Windows fonts; use whatever is installed instead
+            let sans_serif = first_installed(fontdb, SANS_SERIF_FONTS);
+            let monospace = first_installed(fontdb, MONOSPACE_FONTS);
+            let serif = first_installed(fontdb, SERIF_FONTS);
+            if let Some(family) = sans_serif {
+                fontdb.set_sans_serif_family(family);
+            }
+            if let Some(family) = monospace {
+                fontdb.set_monospace_family(family);
+            }
+            if let Some(family) = serif {
+                fontdb.set_serif_family(family);
+                opt.font_family = family.to_string();
+            }
+        }
+        opt
+    }
+}
+
+/// Reads a user stylesheet for `--stylesheet`
+pub fn read_stylesheet(path: &str) -> Result<String> {
+    fs::read_to_string(path)
+        .map_err(|err| FavisError::read_failed(format!("stylesheet {path}"), err))
+}
+
+/// The first of `candidates` that has a loaded font face
+fn first_installed(
+    fontdb: &usvg::fontdb::Database,
+    candidates: &[&'static str],
+) -> Option<&'static str> {
+    candidates.iter().copied().find(|name| {
+        fontdb
+            .faces()
+            .any(|face| face.families.iter().any(|(family, _)| family == name))
+    })
+}
+
+/// Whether the SVG has text that needs fonts.
+///
+/// Looks at the parsed document rather than the bytes, so prefixed elements (`<svg:text>`),
+/// text only drawn through `<use>` and compressed SVGZ are all found. Embedded SVG images
+/// may carry text of their own, so they count too.
+fn has_text(svg_data: &[u8]) -> bool {
+    svg_elements(svg_data, |name, href| match name {
+        "text" => true,
+        "image" => href.is_some_and(is_svg_href),
+        _ => false,
+    })
+    .is_some_and(|found| found.contains(&true))
+}
+
+/// Files outside the SVG that change how it renders: every file in `font_dirs` and the
+/// local files `<image>` elements link to, resolved against `resources_dir`
+pub fn linked_files(svg_data: &[u8], options: &SvgOptions) -> Vec<PathBuf> {
+    let base = options.resources_dir.clone().unwrap_or_default();
+    let mut files: Vec<PathBuf> = svg_elements(svg_data, |name, href| match (name, href) {
+        ("image", Some(href)) if !href.starts_with("data:") && !href.contains("://") => {
+            Some(base.join(href))
+        }
+        _ => None,
+    })
+    .unwrap_or_default()
+    .into_iter()
+    .flatten()
+    .collect();
+    for dir in &options.font_dirs {
+        files_in(dir, &mut files);
+    }
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Every file under `dir`, recursively, as fontdb loads font directories
+fn files_in(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if path.is_dir() {
+            files_in(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Calls `visit` with the local name and `href` of every SVG element, or returns `None` if
+/// the document doesn't parse
+fn svg_elements<T>(svg_data: &[u8], visit: impl Fn(&str, Option<&str>) -> T) -> Option<Vec<T>> {
+    let data = decompressed(svg_data).ok()?;
+    let text = std::str::from_utf8(&data).ok()?;
+    // Same DTD handling as usvg, so entity-defined content is seen
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = roxmltree::Document::parse_with_options(text, options).ok()?;
+    let found = document
+        .descendants()
+        .filter(|node| node.tag_name().namespace() == Some(SVG_NAMESPACE))
+        .map(|node| {
+            let href = node
+                .attribute((XLINK_NAMESPACE, "href"))
+                .or(node.attribute("href"));
+            visit(node.tag_name().name(), href.map(str::trim))
+        })
+        .collect();
+    Some(found)
+}
+
+/// The SVG markup, inflated first if `svg_data` is gzip-compressed SVGZ
+fn decompressed(svg_data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if svg_data.starts_with(&[0x1f, 0x8b]) {
+        Ok(Cow::Owned(usvg::decompress_svgz(svg_data)?))
+    } else {
+        Ok(Cow::Borrowed(svg_data))
     }
+}
+
+/// Whether an `<image>` reference points at an SVG
+fn is_svg_href(href: &str) -> bool {
+    let href = href.trim().to_ascii_lowercase();
+    href.starts_with("data:image/svg") || href.ends_with(".svg") || href.ends_with(".svgz")
+}
 
-    let opt = usvg::Options::default();
+/// Parses SVG data with the given options, applying the `viewbox` override
+pub fn parse_svg(svg_data: &[u8], options: &SvgOptions) -> Result<Tree> {
+    let opt = options.usvg_options(svg_data);
     let tree = Tree::from_data(svg_data, &opt)?;
+    match &options.viewbox {
+        Some(viewbox) => {
+            let cropped = replace_viewbox(&decompressed(svg_data)?, viewbox, tree.size())?;
+            Ok(Tree::from_data(&cropped, &opt)?)
+        }
+        None => Ok(tree),
+    }
+}
+
+/// Rewrites the root `<svg>` tag so its `viewBox` is `viewbox`.
+///
+/// `width` and `height` are replaced too, so the canvas takes the crop's proportions
+/// instead of letterboxing it, at the same pixels per unit as the original `size`.
+fn replace_viewbox(svg_data: &[u8], viewbox: &ViewBox, size: usvg::Size) -> Result<Vec<u8>> {
+    let (start, end) = root_tag(svg_data)
+        .ok_or_else(|| FavisError::invalid_svg("Cannot find the root <svg> element"))?;
+    let tag = std::str::from_utf8(&svg_data[start..end]).map_err(|err| {
+        FavisError::invalid_svg("The root <svg> element is not valid UTF-8").with_source(err)
+    })?;
+
+    let self_closing = tag.ends_with("/>");
+    let body = tag
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim_end_matches('/');
+    let name_len = body.find(|c: char| c.is_whitespace()).unwrap_or(body.len());
+    let mut rewritten = body[..name_len].to_string();
+    // Without a viewBox of its own, one user unit is one pixel
+    let mut scale = (1.0, 1.0);
+    for (name, value, attribute) in attributes(&body[name_len..]) {
+        match name {
+            "viewBox" => {
+                if let Ok(original) = value.parse::<ViewBox>() {
+                    scale = (
+                        size.width() / original.width,
+                        size.height() / original.height,
+                    );
+                }
+            }
+            "width" | "height" => {}
+            _ => {
+                rewritten.push(' ');
+                rewritten.push_str(attribute);
+            }
+        }
+    }
+    let ViewBox {
+        x,
+        y,
+        width,
+        height,
+    } = viewbox;
+    rewritten = format!(
+        "<{rewritten} viewBox=\"{x} {y} {width} {height}\" width=\"{}\" height=\"{}\"{}>",
+        width * scale.0,
+        height * scale.1,
+        if self_closing { "/" } else { "" }
+    );
+
+    let mut data = Vec::with_capacity(svg_data.len() + 32);
+    data.extend_from_slice(&svg_data[..start]);
+    data.extend_from_slice(rewritten.as_bytes());
+    data.extend_from_slice(&svg_data[end..]);
+    Ok(data)
+}
+
+/// Byte range of the root element's start tag, skipping the prolog and comments
+fn root_tag(data: &[u8]) -> Option<(usize, usize)> {
+    let find = |from: usize, needle: &[u8]| {
+        data[from..]
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .map(|i| from + i + needle.len())
+    };
+    let mut pos = 0;
+    loop {
+        let start = pos + data[pos..].iter().position(|&b| b == b'<')?;
+        let rest = &data[start..];
+        pos = if rest.starts_with(b"<!--") {
+            find(start, b"-->")?
+        } else if rest.starts_with(b"<?") {
+            find(start, b"?>")?
+        } else if rest.starts_with(b"<!") {
+            find(start, b">")?
+        } else {
+            break;
+        };
+    }
+    let start = pos + data[pos..].iter().position(|&b| b == b'<')?;
+
+    // The tag ends at the first `>` outside a quoted attribute value
+    let mut quote = None;
+    for (i, &b) in data[start..].iter().enumerate() {
+        match (quote, b) {
+            (None, b'"' | b'\'') => quote = Some(b),
+            (Some(q), _) if q == b => quote = None,
+            (None, b'>') => return Some((start, start + i + 1)),
+            _ => {}
+        }
+    }
+    None
+}
 
+/// Splits the attribute part of a start tag into `(name, value, name="value")` triples
+fn attributes(mut text: &str) -> Vec<(&str, &str, &str)> {
+    let mut found = Vec::new();
+    loop {
+        text = text.trim_start();
+        let Some(eq) = text.find('=') else {
+            return found;
+        };
+        let name = text[..eq].trim_end();
+        let value = text[eq + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            return found;
+        };
+        let Some(close) = value[1..].find(quote) else {
+            return found;
+        };
+        let end = text.len() - value.len() + close + 2;
+        found.push((name, &value[1..close + 1], &text[..end]));
+        text = &text[end..];
+    }
+}
+
+/// Render a parsed SVG to a pixmap, scaled by `scale`.
+pub fn render_svg(
+    tree: &Tree,
+    scale: f32,
+    limits: &Limits,
+    progress: Option<&ProgressBar>,
+) -> Result<Pixmap> {
+    let size = tree.size();
+    let (width, height) = (pixels(size.width() * scale), pixels(size.height() * scale));
+    limits.check("The rendered SVG", width, height)?;
     if let Some(pb) = progress {
         pb.set_message(format!(
             "{} {}x{} {}",
@@ -30,13 +449,12 @@ pub fn render_svg(
         ));
     }
 
-    let mut pixmap = Pixmap::new(width, height).ok_or_else(|| {
-        FavisError::processing_error(format!(
-            "Cannot create {width}x{height} pixmap - insufficient memory"
-        ))
-    })?;
-
-    resvg::render(&tree, usvg::Transform::default(), &mut pixmap.as_mut());
+    let mut pixmap = new_pixmap(width, height)?;
+    resvg::render(
+        tree,
+        usvg::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
 
     if let Some(pb) = progress {
         pb.set_message(format!(
@@ -49,19 +467,104 @@ pub fn render_svg(
     Ok(pixmap)
 }
 
-/// Get the original dimensions of an SVG file
-pub fn get_svg_dimensions(svg_data: &[u8]) -> Result<(u32, u32)> {
-    let opt = usvg::Options::default();
-    let tree = Tree::from_data(svg_data, &opt)?;
+/// Render SVG data as the source of a `target`px icon set.
+///
+/// The SVG (or the `id` element) is scaled so that the square `fit` makes of it is `target`
+/// pixels, whatever size the file declares: tiny icons aren't upscaled afterwards and huge
+/// canvases aren't rendered in full.
+pub fn render_svg_source(
+    svg_data: &[u8],
+    options: &SvgOptions,
+    target: u32,
+    fit: Fit,
+    limits: &Limits,
+    progress: Option<&ProgressBar>,
+) -> Result<Pixmap> {
+    if let Some(pb) = progress {
+        pb.set_message(format!("{}", "Parsing SVG data...".cyan().bold()));
+    }
+    let tree = parse_svg(svg_data, options)?;
+    let Some(id) = &options.id else {
+        let size = tree.size();
+        let scale = target as f32 / fit::framed_side(size.width(), size.height(), fit);
+        return render_svg(&tree, scale, limits, progress);
+    };
 
-    let size = tree.size();
-    Ok((size.width() as u32, size.height() as u32))
+    let node = tree
+        .node_by_id(id)
+        .ok_or_else(|| FavisError::svg_element_not_found(id, &element_ids(tree.root())))?;
+    let bounds = node.abs_layer_bounding_box().ok_or_else(|| {
+        FavisError::invalid_svg(format!("The element #{id} has nothing visible to render"))
+    })?;
+    // With a viewBox override the crop is the whole canvas; otherwise it's the element's bounds
+    let (width, height, crop) = if options.viewbox.is_some() {
+        let size = tree.size();
+        (size.width(), size.height(), usvg::Transform::default())
+    } else {
+        let crop = usvg::Transform::from_translate(-bounds.x(), -bounds.y());
+        (bounds.width(), bounds.height(), crop)
+    };
+    let scale = target as f32 / fit::framed_side(width, height, fit);
+    // `render_node` only applies the element's own transform and moves its bounds to the
+    // origin, so put back its ancestors' transforms and undo the move
+    let transform = usvg::Transform::from_scale(scale, scale)
+        .pre_concat(crop)
+        .pre_concat(parent_transform(node))
+        .pre_translate(bounds.x(), bounds.y());
+
+    let (width, height) = (pixels(width * scale), pixels(height * scale));
+    limits.check(&format!("The rendered element #{id}"), width, height)?;
+    if let Some(pb) = progress {
+        pb.set_message(format!(
+            "{} {} {}",
+            "Rendering SVG element".cyan().bold(),
+            format!("#{id}").yellow(),
+            "...".cyan().bold()
+        ));
+    }
+    let mut pixmap = new_pixmap(width, height)?;
+    resvg::render_node(node, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+/// Whole pixels needed to cover `length`; fractional sizes are rounded up so edge pixels
+/// aren't cut off, but float noise like 512.00003 isn't
+fn pixels(length: f32) -> u32 {
+    ((length - 0.001).ceil() as u32).max(1)
 }
 
-/// Render SVG data to a pixmap at its original size.
-pub fn render_svg_original_size(svg_data: &[u8], progress: Option<&ProgressBar>) -> Result<Pixmap> {
-    let (width, height) = get_svg_dimensions(svg_data)?;
-    render_svg(svg_data, width, height, progress)
+fn new_pixmap(width: u32, height: u32) -> Result<Pixmap> {
+    Pixmap::new(width, height).ok_or_else(|| {
+        FavisError::processing_error(format!(
+            "Cannot create {width}x{height} pixmap - insufficient memory"
+        ))
+    })
+}
+
+/// Transform from the element's parent to the canvas
+fn parent_transform(node: &usvg::Node) -> usvg::Transform {
+    match node {
+        usvg::Node::Group(group) => group
+            .transform()
+            .invert()
+            .map(|own| group.abs_transform().pre_concat(own))
+            .unwrap_or_default(),
+        _ => node.abs_transform(),
+    }
+}
+
+/// Every element id in the rendered tree, for suggesting alternatives
+fn element_ids(group: &usvg::Group) -> Vec<String> {
+    let mut ids = Vec::new();
+    for node in group.children() {
+        if !node.id().is_empty() {
+            ids.push(node.id().to_string());
+        }
+        if let usvg::Node::Group(child) = node {
+            ids.extend(element_ids(child));
+        }
+    }
+    ids
 }
 
 /// Extension trait for Pixmap operations
@@ -71,16 +574,171 @@ pub trait PixmapExt {
 
 impl PixmapExt for Pixmap {
     fn to_dynamic_image(&self) -> Result<image::DynamicImage> {
-        // Convert Pixmap to image::DynamicImage
+        // tiny-skia stores premultiplied RGBA; image expects straight alpha
         let width = self.width();
         let height = self.height();
-        let data = self.data();
+        let data: Vec<u8> = self
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
 
-        // Create an RgbaImage from the pixmap data
-        let img = image::RgbaImage::from_raw(width, height, data.to_vec()).ok_or_else(|| {
+        // Create an RgbaImage from the demultiplied data
+        let img = image::RgbaImage::from_raw(width, height, data).ok_or_else(|| {
             FavisError::processing_error("Cannot convert pixmap data to image format")
         })?;
 
         Ok(image::DynamicImage::ImageRgba8(img))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn svg(body: &str) -> Vec<u8> {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 10 10">{body}</svg>"#
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn finds_text_however_it_is_written() {
+        assert!(has_text(&svg("<text>A</text>")));
+        assert!(has_text(&svg(
+            r##"<defs><text id="t">A</text></defs><use xlink:href="#t"/>"##
+        )));
+        let prefixed =
+            br#"<svg:svg xmlns:svg="http://www.w3.org/2000/svg"><svg:text>A</svg:text></svg:svg>"#;
+        assert!(has_text(prefixed));
+        assert!(has_text(&svg(r#"<image href="logo.svg"/>"#)));
+    }
+
+    #[test]
+    fn ignores_text_that_is_not_an_element() {
+        assert!(!has_text(&svg(
+            "<!-- <text> --><rect width=\"10\" height=\"10\"/>"
+        )));
+        assert!(!has_text(&svg(
+            r#"<rect id="text" width="10" height="10"/>"#
+        )));
+        assert!(!has_text(&svg(r#"<image href="logo.png"/>"#)));
+    }
+
+    #[test]
+    fn parses_viewboxes_with_commas_or_spaces() {
+        let expected = ViewBox {
+            x: -1.0,
+            y: 2.5,
+            width: 10.0,
+            height: 20.0,
+        };
+        assert_eq!("-1,2.5,10,20".parse::<ViewBox>(), Ok(expected));
+        assert_eq!(" -1 2.5, 10  20 ".parse::<ViewBox>(), Ok(expected));
+    }
+
+    #[test]
+    fn refuses_malformed_viewboxes() {
+        for value in [
+            "",
+            "0,0,10",
+            "0,0,10,10,10",
+            "0,0,ten,10",
+            "0,0,0,10",
+            "0,0,10,-5",
+        ] {
+            assert!(value.parse::<ViewBox>().is_err(), "{value:?}");
+        }
+        assert!("0,0,inf,10".parse::<ViewBox>().is_err());
+    }
+
+    fn crop(svg: &str, viewbox: &str, width: f32, height: f32) -> String {
+        let size = usvg::Size::from_wh(width, height).unwrap();
+        let data = replace_viewbox(svg.as_bytes(), &viewbox.parse().unwrap(), size).unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    #[test]
+    fn replaces_the_viewbox_and_keeps_the_pixel_scale() {
+        // 200px for 100 units: the 50x25 crop becomes 100x50 pixels
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100" width="200" height="200" fill="red"><rect/></svg>"#;
+        assert_eq!(
+            crop(svg, "10,10,50,25", 200.0, 200.0),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" fill="red" viewBox="10 10 50 25" width="100" height="50"><rect/></svg>"#
+        );
+    }
+
+    #[test]
+    fn uses_one_pixel_per_unit_without_a_viewbox() {
+        let svg = r#"<svg width="64" height="64"/>"#;
+        assert_eq!(
+            crop(svg, "0 0 32 16", 64.0, 64.0),
+            r#"<svg viewBox="0 0 32 16" width="32" height="16"/>"#
+        );
+    }
+
+    #[test]
+    fn finds_the_root_tag_past_the_prolog() {
+        let svg = concat!(
+            "<?xml version=\"1.0\"?>\n<!-- <svg viewBox=\"0 0 1 1\"> -->\n",
+            "<!DOCTYPE svg>\n<svg data-x=\"a>b\" viewBox=\"0 0 10 10\"><g/></svg>"
+        );
+        let cropped = crop(svg, "0,0,5,5", 10.0, 10.0);
+        assert!(
+            cropped.ends_with(
+                r#"<svg data-x="a>b" viewBox="0 0 5 5" width="5" height="5"><g/></svg>"#
+            ),
+            "{cropped}"
+        );
+        assert!(cropped.contains(r#"<!-- <svg viewBox="0 0 1 1"> -->"#));
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn crops_compressed_svgz_sources() {
+        let svgz = gzip(&svg(r#"<rect width="10" height="10"/>"#));
+        let options = SvgOptions {
+            viewbox: Some("0,0,5,2".parse().unwrap()),
+            ..Default::default()
+        };
+        let tree = parse_svg(&svgz, &options).unwrap();
+        // Without width and height the canvas is the 10x10 viewBox, one pixel per unit
+        assert_eq!((tree.size().width(), tree.size().height()), (5.0, 2.0));
+        assert!(has_text(&gzip(&svg("<text>A</text>"))));
+    }
+
+    #[test]
+    fn lists_linked_images_and_font_files() {
+        let fonts = crate::stage::TempDir::new("fonts");
+        fs::create_dir_all(fonts.path().join("nested")).unwrap();
+        fs::write(fonts.path().join("nested/Brand.ttf"), "").unwrap();
+        let options = SvgOptions {
+            resources_dir: Some(PathBuf::from("assets")),
+            font_dirs: vec![fonts.path().to_path_buf()],
+            ..Default::default()
+        };
+        let data = svg(concat!(
+            r#"<image href="logo.png"/><image xlink:href="../shared/mark.svg"/>"#,
+            r#"<image href="data:image/png;base64,AAAA"/><image href="https://example.com/a.png"/>"#
+        ));
+        assert_eq!(
+            linked_files(&data, &options),
+            [
+                fonts.path().join("nested/Brand.ttf"),
+                PathBuf::from("assets/../shared/mark.svg"),
+                PathBuf::from("assets/logo.png"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! `favis preview`: draws icons in the terminal with half-blocks or a graphics protocol.
+
+use crate::cli::{PreviewBackground, PreviewProtocol};
+use crate::error::{ErrorKind, FavisError, Result};
+use crate::img;
+use crate::limits::Limits;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use owo_colors::OwoColorize;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Cursor, IsTerminal, Write};
+use std::path::Path;
+
+/// Smallest size, in display pixels, icons are scaled up to by default
+const HALFBLOCK_TARGET: u32 = 32;
+const GRAPHICS_TARGET: u32 = 128;
+/// Longest side an icon is scaled up to, whatever `--scale` asks for
+const MAX_DISPLAY_SIDE: u32 = 2048;
+/// Terminal width assumed when `COLUMNS` isn't set
+const DEFAULT_COLUMNS: u32 = 80;
+
+/// Checkerboard colors and square size, in display pixels
+const CHECKER_LIGHT: [u8; 3] = [0xff, 0xff, 0xff];
+const CHECKER_DARK: [u8; 3] = [0xc8, 0xc8, 0xc8];
+const CHECKER_SQUARE: u32 = 4;
+const LIGHT: [u8; 3] = [0xff, 0xff, 0xff];
+const DARK: [u8; 3] = [0x20, 0x21, 0x24];
+
+/// Size of the base64 chunks the kitty protocol accepts
+const KITTY_CHUNK: usize = 4096;
+
+/// Settings for `favis preview`
+#[derive(Debug, Clone, Copy)]
+pub struct PreviewOptions {
+    pub protocol: PreviewProtocol,
+    pub background: PreviewBackground,
+    /// Magnification; picked per icon when not set
+    pub scale: Option<u32>,
+}
+
+/// A decoded icon and what to call it
+struct Icon {
+    label: String,
+    image: RgbaImage,
+}
+
+/// Draws the icon at `path`, or every icon in it if it's a folder; returns how many were drawn
+pub fn run(path: &str, options: &PreviewOptions) -> Result<usize> {
+    let icons = load(Path::new(path))?;
+    let protocol = match options.protocol {
+        PreviewProtocol::Auto => detect_protocol(),
+        protocol => protocol,
+    };
+
+    // Labels go through anstream so their colors are dropped when piped; images go out raw
+    let mut labels = anstream::stdout();
+    let mut stdout = io::stdout();
+    for icon in &icons {
+        let (width, height) = icon.image.dimensions();
+        let image = display_image(&icon.image, protocol, options);
+        let drawn = match protocol {
+            PreviewProtocol::Kitty => kitty(&png_bytes(&image)?),
+            PreviewProtocol::Iterm => iterm(&png_bytes(&image)?, image.dimensions()),
+            PreviewProtocol::Sixel => sixel(&image),
+            PreviewProtocol::Halfblocks | PreviewProtocol::Auto => halfblocks(&image),
+        };
+
+        let written = writeln!(
+            labels,
+            "{}  {}",
+            icon.label.bold(),
+            format!("{width}x{height}").dimmed()
+        )
+        .and_then(|_| labels.flush())
+        .and_then(|_| stdout.write_all(drawn.as_bytes()))
+        .and_then(|_| stdout.write_all(b"\n"))
+        .and_then(|_| stdout.flush());
+        match written {
+            // Piped into `head` or similar, which stopped reading
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+            result => result?,
+        }
+    }
+    Ok(icons.len())
+}
+
+/// Picks a graphics protocol from what the terminal advertises in its environment.
+///
+/// Terminals don't forward most of these over SSH, which falls back to half-blocks.
+fn detect_protocol() -> PreviewProtocol {
+    if !io::stdout().is_terminal() {
+        return PreviewProtocol::Halfblocks;
+    }
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    let program = var("TERM_PROGRAM");
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || program == "ghostty" {
+        PreviewProtocol::Kitty
+    } else if matches!(program.as_str(), "iTerm.app" | "WezTerm") || var("LC_TERMINAL") == "iTerm2"
+    {
+        PreviewProtocol::Iterm
+    } else if term.contains("sixel") || term.starts_with("foot") || term == "mlterm" {
+        PreviewProtocol::Sixel
+    } else {
+        PreviewProtocol::Halfblocks
+    }
+}
+
+/// Loads a PNG or ICO file, or every PNG and ICO in a folder
+fn load(path: &Path) -> Result<Vec<Icon>> {
+    if !path.is_dir() {
+        return load_file(path);
+    }
+    let mut files: Vec<_> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| extension(file).is_some_and(|ext| ext == "png" || ext == "ico"))
+        .collect();
+    files.sort();
+
+    let mut icons = Vec::new();
+    for file in files {
+        icons.extend(load_file(&file)?);
+    }
+    if icons.is_empty() {
+        return Err(FavisError::file_not_found(format!(
+            "No PNG or ICO files in {}",
+            path.display()
+        )));
+    }
+    // Smallest first, like the sizes on a contact sheet; ICO entries after the PNGs
+    icons.sort_by_key(|icon| (icon.label.contains(".ico"), icon.image.width()));
+    Ok(icons)
+}
+
+fn load_file(path: &Path) -> Result<Vec<Icon>> {
+    let bytes =
+        fs::read(path).map_err(|err| FavisError::read_failed(path.to_string_lossy(), err))?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // Oversized files are reported as such; anything else that won't decode isn't previewable
+    let limits = Limits::default();
+    let unsupported = |err: FavisError| match err.kind {
+        ErrorKind::LimitExceeded => err,
+        _ => FavisError::unsupported_preview(&name).with_source(err),
+    };
+
+    if extension(path).is_some_and(|ext| ext == "ico") {
+        let images = img::decode_ico(&bytes, &name, &limits).map_err(unsupported)?;
+        return Ok(images
+            .into_iter()
+            .map(|image| Icon {
+                label: name.clone(),
+                image,
+            })
+            .collect());
+    }
+
+    let image = img::decode_rgba(&bytes, &name, &limits).map_err(unsupported)?;
+    Ok(vec![Icon { label: name, image }])
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+/// Scales the icon for display and flattens it onto the background
+fn display_image(
+    image: &RgbaImage,
+    protocol: PreviewProtocol,
+    options: &PreviewOptions,
+) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let halfblocks = matches!(
+        protocol,
+        PreviewProtocol::Halfblocks | PreviewProtocol::Auto
+    );
+    let target = if halfblocks {
+        HALFBLOCK_TARGET
+    } else {
+        GRAPHICS_TARGET
+    };
+    let longest = width.max(height).max(1);
+    // Large icons aren't magnified past MAX_DISPLAY_SIDE, which also keeps the multiplication
+    // below from overflowing
+    let scale = options
+        .scale
+        .unwrap_or_else(|| (target / longest).max(1))
+        .min((MAX_DISPLAY_SIDE / longest).max(1));
+
+    let mut scaled = imageops::resize(image, width * scale, height * scale, FilterType::Nearest);
+    // Half-blocks draw one pixel per column, so wide icons are shrunk to fit the terminal
+    let columns = env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(DEFAULT_COLUMNS);
+    if halfblocks && scaled.width() > columns {
+        let height = scaled.height() * columns / scaled.width();
+        scaled = imageops::resize(&scaled, columns, height.max(1), FilterType::Triangle);
+    }
+
+    for (x, y, pixel) in scaled.enumerate_pixels_mut() {
+        let backdrop = match options.background {
+            PreviewBackground::Light => LIGHT,
+            PreviewBackground::Dark => DARK,
+            PreviewBackground::Checker
+                if (x / CHECKER_SQUARE + y / CHECKER_SQUARE).is_multiple_of(2) =>
+            {
+                CHECKER_LIGHT
+            }
+            PreviewBackground::Checker => CHECKER_DARK,
+        };
+        let alpha = pixel[3] as u32;
+        let blend =
+            |c: usize| ((pixel[c] as u32 * alpha + backdrop[c] as u32 * (255 - alpha)) / 255) as u8;
+        *pixel = Rgba([blend(0), blend(1), blend(2), 255]);
+    }
+    scaled
+}
+
+/// Two pixels per character cell: `▀` in the top pixel's color over the bottom one's
+fn halfblocks(image: &RgbaImage) -> String {
+    let mut out = String::new();
+    let (width, height) = image.dimensions();
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let [r, g, b, _] = image.get_pixel(x, y).0;
+            let _ = write!(out, "\x1b[38;2;{r};{g};{b}m");
+            if y + 1 < height {
+                let [r, g, b, _] = image.get_pixel(x, y + 1).0;
+                let _ = write!(out, "\x1b[48;2;{r};{g};{b}m");
+            } else {
+                out.push_str("\x1b[49m");
+            }
+            out.push('▀');
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+fn png_bytes(image: &RgbaImage) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Kitty graphics protocol: a PNG sent in base64 chunks
+fn kitty(png: &[u8]) -> String {
+    let encoded = BASE64.encode(png);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).expect("base64 is ASCII");
+        if i == 0 {
+            let _ = write!(out, "\x1b_Ga=T,f=100,m={more};{chunk}\x1b\\");
+        } else {
+            let _ = write!(out, "\x1b_Gm={more};{chunk}\x1b\\");
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// iTerm2 inline image escape
+fn iterm(png: &[u8], (width, height): (u32, u32)) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={width}px;height={height}px;preserveAspectRatio=1:{}\x07\n",
+        png.len(),
+        BASE64.encode(png)
+    )
+}
+
+/// Sixel graphics: exact colors when there are at most 256, else a 6x6x6 color cube
+fn sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let mut colors: Vec<[u8; 3]> = Vec::new();
+    let mut index: HashMap<[u8; 3], usize> = HashMap::new();
+    for pixel in image.pixels() {
+        let rgb = [pixel[0], pixel[1], pixel[2]];
+        index.entry(rgb).or_insert_with(|| {
+            colors.push(rgb);
+            colors.len() - 1
+        });
+    }
+    let cube = colors.len() > 256;
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let color_of = |pixel: &Rgba<u8>| {
+        let rgb = [pixel[0], pixel[1], pixel[2]];
+        if cube {
+            (level(rgb[0]) * 36 + level(rgb[1]) * 6 + level(rgb[2])) as usize
+        } else {
+            index[&rgb]
+        }
+    };
+    let palette: Vec<[u32; 3]> = if cube {
+        (0..216)
+            .map(|i| [i / 36, i / 6 % 6, i % 6].map(|level| level * 100 / 5))
+            .collect()
+    } else {
+        colors
+            .iter()
+            .map(|rgb| rgb.map(|c| c as u32 * 100 / 255))
+            .collect()
+    };
+
+    let mut out = format!("\x1bPq\"1;1;{width};{height}");
+    for (i, [r, g, b]) in palette.iter().enumerate() {
+        let _ = write!(out, "#{i};2;{r};{g};{b}");
+    }
+    for band in (0..height).step_by(6) {
+        let rows = (band..(band + 6).min(height)).collect::<Vec<_>>();
+        let mut used: Vec<usize> = rows
+            .iter()
+            .flat_map(|&y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| color_of(image.get_pixel(x, y)))
+            .collect();
+        used.sort_unstable();
+        used.dedup();
+
+        for color in used {
+            let _ = write!(out, "#{color}");
+            let sixels: Vec<u8> = (0..width)
+                .map(|x| {
+                    let bits = rows.iter().enumerate().fold(0u8, |bits, (k, &y)| {
+                        if color_of(image.get_pixel(x, y)) == color {
+                            bits | 1 << k
+                        } else {
+                            bits
+                        }
+                    });
+                    63 + bits
+                })
+                .collect();
+            // Run-length encode repeated sixels
+            let mut x = 0;
+            while x < sixels.len() {
+                let run = sixels[x..].iter().take_while(|&&s| s == sixels[x]).count();
+                if run > 3 {
+                    let _ = write!(out, "!{run}{}", sixels[x] as char);
+                } else {
+                    out.extend(std::iter::repeat_n(sixels[x] as char, run));
+                }
+                x += run;
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_two_pixels_per_cell() {
+        let image = RgbaImage::from_fn(2, 2, |x, y| match (x, y) {
+            (0, 0) => Rgba([255, 0, 0, 255]),
+            (1, 0) => Rgba([0, 255, 0, 255]),
+            (0, 1) => Rgba([0, 0, 255, 255]),
+            _ => Rgba([1, 2, 3, 255]),
+        });
+        assert_eq!(
+            halfblocks(&image),
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\
+             \x1b[38;2;0;255;0m\x1b[48;2;1;2;3m▀\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn leaves_the_background_of_an_odd_last_row() {
+        let image = RgbaImage::from_pixel(1, 3, Rgba([9, 8, 7, 255]));
+        assert_eq!(
+            halfblocks(&image),
+            "\x1b[38;2;9;8;7m\x1b[48;2;9;8;7m▀\x1b[0m\n\
+             \x1b[38;2;9;8;7m\x1b[49m▀\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Watch mode: re-run the generate pipeline whenever the source or config changes.
+
+use crate::error::{FavisError, Result};
+use crate::generate::{self, FileStatus, GenerateOptions, GenerateReport};
+use crate::output::Output;
+use indicatif::ProgressBar;
+use notify::{RecursiveMode, Watcher};
+use owo_colors::OwoColorize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Quiet period after the last change before a run starts
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How often the loop checks for Ctrl+C and finished runs
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Absolute form of a path, falling back to the path itself
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// A generate run in progress on its own thread
+struct Run {
+    handle: JoinHandle<Result<GenerateReport>>,
+    /// Cancels this run only
+    stop: Arc<AtomicBool>,
+}
+
+impl Run {
+    /// Asks the run to stop and waits for it, discarding its result
+    fn cancel(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+/// Shows the outcome of a finished run without stopping the spinner
+fn report_run(result: Result<GenerateReport>, spinner: &ProgressBar, output: &Output) {
+    let watching = format!("{}", "Watching for changes...".cyan().bold());
+    match result {
+        Ok(report) if output.json => output.result("generate", &report),
+        Ok(report) => {
+            for warning in &report.warnings {
+                output.warn(spinner, warning);
+            }
+            let summary = format!(
+                "Updated ({} written, {} unchanged).",
+                report.count(FileStatus::Written),
+                report.count(FileStatus::Unchanged)
+            );
+            output.status(
+                spinner,
+                format!(
+                    "{} {} {watching}",
+                    "✓".green().bold(),
+                    summary.green().bold()
+                ),
+            );
+        }
+        Err(err) => {
+            spinner.suspend(|| output.error("generate", &err));
+            output.status(spinner, watching);
+        }
+    }
+}
+
+/// Watches `paths` and regenerates on every (debounced) change until Ctrl+C.
+///
+/// `build_options` is called before each run so config file edits are picked up.
+/// Each run gets its own cancel flag: a change that arrives during a run sets it, and so
+/// does Ctrl+C (the process-wide `cancelled` flag), which also stops the loop.
+pub fn watch(
+    paths: &[PathBuf],
+    build_options: impl Fn() -> Result<GenerateOptions>,
+    spinner: &ProgressBar,
+    output: Output,
+    cancelled: Arc<AtomicBool>,
+) -> Result<()> {
+    let watched: Vec<PathBuf> = paths.iter().map(|p| absolute(p)).collect();
+
+    // Watch parent directories: editors often replace files instead of writing in place
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| FavisError::processing_error(format!("Cannot start file watcher: {e}")))?;
+    let mut dirs: Vec<PathBuf> = watched
+        .iter()
+        .filter_map(|p| p.parent().map(Path::to_path_buf))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    for dir in &dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| {
+                FavisError::processing_error(format!("Cannot watch {}: {e}", dir.display()))
+            })?;
+    }
+
+    let start_run = |options: GenerateOptions| -> Run {
+        let spinner = spinner.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let run_stop = stop.clone();
+        let handle = thread::spawn(move || generate::run(&options, &spinner, run_stop));
+        Run { handle, stop }
+    };
+
+    // Run once right away, then on every change
+    let mut pending: Option<Instant> = Some(Instant::now() - DEBOUNCE);
+    let mut running: Option<Run> = None;
+
+    loop {
+        // Ctrl+C: stop the run and the loop
+        if cancelled.load(Ordering::Relaxed) {
+            if let Some(run) = running.take() {
+                run.cancel();
+            }
+            output.finish(spinner, format!("{}", "Stopped watching.".cyan().bold()));
+            return Ok(());
+        }
+
+        // Collect change events for the watched files
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                let relevant = event
+                    .paths
+                    .iter()
+                    .any(|changed| watched.iter().any(|w| absolute(changed) == *w));
+                if relevant && !event.kind.is_access() {
+                    pending = Some(Instant::now());
+                }
+            }
+            Ok(Err(e)) => spinner.suspend(|| {
+                let err = FavisError::processing_error(format!("File watcher error: {e}"));
+                output.error("generate", &err)
+            }),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(FavisError::processing_error(
+                    "File watcher stopped unexpectedly",
+                ));
+            }
+        }
+
+        // Report runs that finished on their own
+        if running.as_ref().is_some_and(|run| run.handle.is_finished()) {
+            if let Some(run) = running.take() {
+                let result = run.handle.join().unwrap_or_else(|_| {
+                    Err(FavisError::processing_error("Generation thread panicked"))
+                });
+                report_run(result, spinner, &output);
+            }
+        }
+
+        let due = pending.is_some_and(|at| at.elapsed() >= DEBOUNCE);
+        if !due {
+            continue;
+        }
+        pending = None;
+
+        // A newer change makes the in-flight run pointless: cancel it and wait
+        if let Some(run) = running.take() {
+            run.cancel();
+        }
+
+        match build_options() {
+            Ok(options) => {
+                output.status(
+                    spinner,
+                    format!("{}", "Change detected, regenerating...".cyan().bold()),
+                );
+                running = Some(start_run(options));
+            }
+            Err(err) => report_run(Err(err), spinner, &output),
+        }
+    }
+}